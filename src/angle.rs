@@ -0,0 +1,76 @@
+//! Heading arithmetic. Headings are radians, measured like `atan2`, and every
+//! function here returns them wrapped to `[-PI, PI)`.
+
+use crate::consts::PI;
+
+/// Wraps `angle` to `[-PI, PI)`.
+pub fn wrap(angle: f32) -> f32 {
+    let wrapped = (angle + PI).rem_euclid(2.0 * PI) - PI;
+    // `rem_euclid` can round up to exactly `2 * PI` for tiny negative inputs.
+    if wrapped >= PI {
+        wrapped - 2.0 * PI
+    } else {
+        wrapped
+    }
+}
+
+/// Signed turn from `from` to `to` going the short way round, in `[-PI, PI)`.
+pub fn shortest_diff(from: f32, to: f32) -> f32 {
+    wrap(to - from)
+}
+
+/// Turns `t` of the way from `from` towards `to` along the shorter arc.
+pub fn lerp(from: f32, to: f32, t: f32) -> f32 {
+    wrap(from + shortest_diff(from, to) * t)
+}
+
+/// Turns from `from` towards `to`, but by no more than `max_turn`.
+pub fn clamp_turn(from: f32, to: f32, max_turn: f32) -> f32 {
+    let max_turn = max_turn.max(0.0);
+    wrap(from + shortest_diff(from, to).clamp(-max_turn, max_turn))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        shortest_diff(a, b).abs() < 1e-5
+    }
+
+    #[test]
+    fn wraps_into_range() {
+        for angle in [-7.0 * PI, -PI, -1e-9, 0.0, PI, 3.5 * PI, 100.0] {
+            let wrapped = wrap(angle);
+            assert!(
+                (-PI..PI).contains(&wrapped),
+                "{} wrapped to {}",
+                angle,
+                wrapped
+            );
+            assert!(((wrapped - angle) / (2.0 * PI)).fract().abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn differences_cross_the_seam_the_short_way() {
+        let diff = shortest_diff(PI - 0.1, -PI + 0.1);
+        assert!((diff - 0.2).abs() < 1e-5);
+        let diff = shortest_diff(-PI + 0.1, PI - 0.1);
+        assert!((diff + 0.2).abs() < 1e-5);
+    }
+
+    #[test]
+    fn lerps_along_the_shorter_arc() {
+        assert!(close(lerp(PI - 0.2, -PI + 0.2, 0.5), PI));
+        assert!(close(lerp(0.3, 1.3, 0.25), 0.55));
+        assert!(close(lerp(0.3, 1.3, 1.0), 1.3));
+    }
+
+    #[test]
+    fn clamps_turns() {
+        assert!(close(clamp_turn(PI - 0.1, -PI + 0.5, 0.2), -PI + 0.1));
+        assert!(close(clamp_turn(0.0, -1.0, 0.25), -0.25));
+        assert!(close(clamp_turn(0.0, 0.1, 0.25), 0.1));
+    }
+}
@@ -1,243 +1,218 @@
-use crate::{
-    consts::{
-        ANT_COLOR, ANT_COUNT, ANT_PHEROMONE_TIMER, ANT_SIZE, NEST_HONING_STRENGTH, PI,
-        WANDER_COEFFICIENT,
-    },
-    functions::{compile_shader, draw_points, initialize_ants, link_program, next_ant_position},
-    grid::{GridRenderer, GridResource}, pheromones::PheromoneRenderer,
-};
-use rand::prelude::*;
-use rand_xoshiro::Xoshiro256Plus;
-use wasm_bindgen::JsCast;
-use wasm_bindgen::JsValue;
-use web_sys::{WebGlBuffer, console};
-use web_sys::WebGlVertexArrayObject;
-use web_sys::{WebGl2RenderingContext, WebGlProgram, WebGlUniformLocation};
-
-// #[derive(Clone, Debug)]
-// pub struct Ant {
-//     pub x: f32,
-//     pub y: f32,
-//     pub dir: f32,
-//     pub has_food: bool,
-// }
-
-pub struct AntRenderer {
-    ants: Vec<f32>,
-    dirs: Vec<f32>,
-    has_food: Vec<bool>,
-    pheromone_timer: usize,
-    program: WebGlProgram,
-    width: f32,
-    height: f32,
-    // a_position_location: i32,
-    u_resolution_location: Option<WebGlUniformLocation>,
-    u_ant_size_location: Option<WebGlUniformLocation>,
-    u_color_location: Option<WebGlUniformLocation>,
-    position_buffer: WebGlBuffer,
-    positions_array_buf_view: js_sys::Float32Array,
-    vertex_count: i32,
-    vao: WebGlVertexArrayObject,
-}
-
-impl AntRenderer {
-    pub fn new(gl: &WebGl2RenderingContext, width: f32, height: f32) -> Result<Self, JsValue> {
-        let vertex_shader = compile_shader(
-            &gl,
-            WebGl2RenderingContext::VERTEX_SHADER,
-            r##"#version 300 es
-
-            in vec2 a_position;
-            uniform vec2 u_resolution;
-            uniform float u_ant_size;
-
-            void main() {
-                vec2 clip_space = 2.0 * a_position / u_resolution - 1.0;
-                gl_Position = vec4(clip_space * vec2(1, -1), 0, 1);
-                gl_PointSize = u_ant_size;
-            }
-            "##,
-        )
-        .expect("Error creating vertex shader");
-
-        let fragment_shader = compile_shader(
-            &gl,
-            WebGl2RenderingContext::FRAGMENT_SHADER,
-            r##"#version 300 es
-            
-            precision highp float;
-            uniform vec4 u_color;
-            out vec4 out_color;
-
-            void main() {
-                out_color = u_color;
-            }
-            "##,
-        )
-        .expect("Error creating fragment shader");
-
-        let (ants, dirs, has_food) = initialize_ants(width, height, ANT_COUNT);
-
-        let program = link_program(&gl, &vertex_shader, &fragment_shader)?;
-
-        let a_position_location = gl.get_attrib_location(&program, "a_position");
-
-        let u_resolution_location = gl.get_uniform_location(&program, "u_resolution");
-        let u_ant_size_location = gl.get_uniform_location(&program, "u_ant_size");
-        let u_color_location = gl.get_uniform_location(&program, "u_color");
-
-        let position_buffer = gl.create_buffer().ok_or("Failed to create buffer")?;
-        gl.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(&position_buffer));
-
-        let location: u32 = ants.as_ptr() as u32 / 4;
-        let next_location = location + ants.len() as u32;
-
-        let memory_buffer = wasm_bindgen::memory()
-            .dyn_into::<js_sys::WebAssembly::Memory>()
-            .unwrap()
-            .buffer();
-
-        let positions_array_buf_view =
-            js_sys::Float32Array::new(&memory_buffer).subarray(location, next_location);
-
-        gl.buffer_data_with_array_buffer_view(
-            WebGl2RenderingContext::ARRAY_BUFFER,
-            &positions_array_buf_view,
-            WebGl2RenderingContext::DYNAMIC_DRAW,
-        );
-
-        let vao = gl
-            .create_vertex_array()
-            .ok_or("Could not create vertex array object")?;
-        gl.bind_vertex_array(Some(&vao));
-
-        gl.vertex_attrib_pointer_with_i32(
-            a_position_location as u32,
-            2,
-            WebGl2RenderingContext::FLOAT,
-            false,
-            0,
-            0,
-        );
-        gl.enable_vertex_attrib_array(a_position_location as u32);
-
-        let vertex_count = (ants.len() / 2) as i32;
-
-        Ok(AntRenderer {
-            ants,
-            dirs,
-            has_food,
-            pheromone_timer: ANT_PHEROMONE_TIMER,
-            width,
-            height,
-            program,
-            // a_position_location,
-            u_resolution_location,
-            u_ant_size_location,
-            u_color_location,
-            position_buffer,
-            positions_array_buf_view,
-            vertex_count,
-            vao,
-        })
-    }
-
-    pub fn render(
-        &mut self,
-        gl: &WebGl2RenderingContext,
-        rng: &mut Xoshiro256Plus,
-        grid_renderer: &GridRenderer,
-        pheromone_renderer: &mut PheromoneRenderer,
-    ) {
-        gl.use_program(Some(&self.program));
-        gl.bind_vertex_array(Some(&self.vao));
-
-        gl.uniform4fv_with_f32_array(self.u_color_location.as_ref(), ANT_COLOR);
-        gl.uniform2f(self.u_resolution_location.as_ref(), self.width, self.height);
-        gl.uniform1f(self.u_ant_size_location.as_ref(), ANT_SIZE);
-
-        gl.bind_buffer(
-            WebGl2RenderingContext::ARRAY_BUFFER,
-            Some(&self.position_buffer),
-        );
-
-        gl.buffer_data_with_array_buffer_view(
-            WebGl2RenderingContext::ARRAY_BUFFER,
-            &self.positions_array_buf_view,
-            WebGl2RenderingContext::DYNAMIC_DRAW,
-        );
-
-        draw_points(&gl, self.vertex_count);
-
-        self.pheromone_timer -= 1;
-
-        for idx in (0..self.ants.len()).step_by(2) {
-            let (part1, part2) = self.ants.split_at_mut(idx + 1);
-            let x = part1.last_mut().expect("Error indexing vector");
-            let y = part2.first_mut().expect("Error indexing vector");
-            let dir = &mut self.dirs[idx / 2];
-            if *dir >= PI {
-                *dir -= 2.0 * PI;
-            }
-            if *dir < -1.0 * PI {
-                *dir += 2.0 * PI;
-            }
-            let mut next_dir = dir.clone();
-            if self.has_food[idx / 2] == true {
-                let dir_diff = grid_renderer.dir_to_nest((*x, *y)) - next_dir;
-                next_dir = next_dir + dir_diff * NEST_HONING_STRENGTH;
-                if self.pheromone_timer == 0 {
-                    pheromone_renderer.add_pheromone((*x, *y));
-                }
-            }
-            next_dir += (rng.gen::<f32>() - 0.5) * WANDER_COEFFICIENT;
-            let mut next_pos = (*x, *y);
-            for i in 0..4 {
-                next_dir = match i {
-                    0 => next_dir,
-                    1 => *dir - 2.0 * *dir,
-                    2 => *dir + (PI / 2.0 - *dir) * 2.0,
-                    _ => *dir + PI,
-                };
-                next_pos = next_ant_position((*x, *y), next_dir);
-                match grid_renderer.get_resource_at_position(next_pos) {
-                    GridResource::Blank => {
-                        break;
-                    }
-                    GridResource::Food => {
-                        if self.has_food[idx / 2] {
-                            break;
-                        }
-                        self.has_food[idx / 2] = true;
-                        next_dir = *dir + PI;
-                        next_pos = next_ant_position((*x, *y), next_dir);
-                        if grid_renderer.get_resource_at_position(next_pos) != GridResource::Blank {
-                            next_pos = next_ant_position((*x, *y), *dir);
-                            next_dir = *dir;
-                        }
-                        break;
-                    }
-                    GridResource::Nest => {
-                        self.has_food[idx / 2] = false;
-                        next_dir = *dir + PI;
-                        next_pos = next_ant_position((*x, *y), next_dir);
-                        if grid_renderer.get_resource_at_position(next_pos) != GridResource::Blank {
-                            next_pos = next_ant_position((*x, *y), *dir);
-                            next_dir = *dir;
-                        }
-                        break;
-                    }
-                    GridResource::Wall => {
-                        continue;
-                    }
-                }
-            }
-            (*x, *y) = next_pos;
-            *dir = next_dir;
-        }
-        
-        if self.pheromone_timer <= 0 {
-            self.pheromone_timer = ANT_PHEROMONE_TIMER;
-        }
-    }
-}
+use crate::{
+    consts::{ANT_SIZE, CARRIED_FOOD_COLOR, CASTE_COLORS, COLONY_COLORS},
+    simulation::Simulation,
+    webgl::{compile_shader, draw_instanced_triangles, float32_array_view, link_program},
+};
+use std::cell::RefCell;
+use wasm_bindgen::JsValue;
+use web_sys::WebGlBuffer;
+use web_sys::WebGlVertexArrayObject;
+use web_sys::{WebGl2RenderingContext, WebGlProgram, WebGlUniformLocation};
+
+/// Triangles drawn for every ant, in units of `ANT_SIZE` with x pointing along
+/// its heading. The third value marks the food dot held in front of the head.
+const ANT_SHAPE: [f32; 27] = [
+    // Body
+    1.0, 0.0, 0.0, -1.0, 0.6, 0.0, -1.0, -0.6, 0.0, //
+    // Food dot
+    0.85, 0.0, 1.0, 1.3, 0.45, 1.0, 1.3, -0.45, 1.0, //
+    1.75, 0.0, 1.0, 1.3, -0.45, 1.0, 1.3, 0.45, 1.0,
+];
+
+pub struct AntRenderer {
+    program: WebGlProgram,
+    u_resolution_location: Option<WebGlUniformLocation>,
+    u_ant_size_location: Option<WebGlUniformLocation>,
+    u_caste_colors_location: Option<WebGlUniformLocation>,
+    u_colony_colors_location: Option<WebGlUniformLocation>,
+    u_food_color_location: Option<WebGlUniformLocation>,
+    position_buffer: WebGlBuffer,
+    dir_buffer: WebGlBuffer,
+    kind_buffer: WebGlBuffer,
+    kinds: RefCell<Vec<f32>>,
+    vao: WebGlVertexArrayObject,
+}
+
+impl AntRenderer {
+    pub fn new(gl: &WebGl2RenderingContext) -> Result<Self, JsValue> {
+        let vertex_shader = compile_shader(
+            gl,
+            WebGl2RenderingContext::VERTEX_SHADER,
+            r##"#version 300 es
+
+            in vec3 a_vertex;
+            in vec2 a_position;
+            in float a_dir;
+            in vec3 a_kind;
+            uniform vec2 u_resolution;
+            uniform float u_ant_size;
+            uniform vec4 u_caste_colors[3];
+            uniform vec4 u_colony_colors[4];
+            uniform vec4 u_food_color;
+            out vec4 v_color;
+
+            void main() {
+                float carrying = a_kind.z;
+                // The food dot collapses to a point on ants that carry nothing.
+                vec2 local = a_vertex.xy * u_ant_size * mix(1.0, carrying, a_vertex.z);
+                vec2 forward = vec2(cos(a_dir), sin(a_dir));
+                vec2 side = vec2(-forward.y, forward.x);
+                vec2 position = a_position + forward * local.x + side * local.y;
+
+                vec2 clip_space = 2.0 * position / u_resolution - 1.0;
+                gl_Position = vec4(clip_space * vec2(1, -1), 0, 1);
+                int colony = int(a_kind.x + 0.5);
+                int caste = int(a_kind.y + 0.5);
+                vec4 body = u_caste_colors[caste] * u_colony_colors[colony % 4];
+                body = mix(body, u_food_color, 0.3 * carrying);
+                v_color = mix(body, u_food_color, a_vertex.z);
+            }
+            "##,
+        )
+        .expect("Error creating vertex shader");
+
+        let fragment_shader = compile_shader(
+            gl,
+            WebGl2RenderingContext::FRAGMENT_SHADER,
+            r##"#version 300 es
+
+            precision highp float;
+            in vec4 v_color;
+            out vec4 out_color;
+
+            void main() {
+                out_color = v_color;
+            }
+            "##,
+        )
+        .expect("Error creating fragment shader");
+
+        let program = link_program(gl, &vertex_shader, &fragment_shader)?;
+
+        let a_vertex_location = gl.get_attrib_location(&program, "a_vertex");
+        let a_position_location = gl.get_attrib_location(&program, "a_position");
+        let a_dir_location = gl.get_attrib_location(&program, "a_dir");
+        let a_kind_location = gl.get_attrib_location(&program, "a_kind");
+
+        let u_resolution_location = gl.get_uniform_location(&program, "u_resolution");
+        let u_ant_size_location = gl.get_uniform_location(&program, "u_ant_size");
+        let u_caste_colors_location = gl.get_uniform_location(&program, "u_caste_colors");
+        let u_colony_colors_location = gl.get_uniform_location(&program, "u_colony_colors");
+        let u_food_color_location = gl.get_uniform_location(&program, "u_food_color");
+
+        let vao = gl
+            .create_vertex_array()
+            .ok_or("Could not create vertex array object")?;
+        gl.bind_vertex_array(Some(&vao));
+
+        let shape_buffer = gl.create_buffer().ok_or("Failed to create buffer")?;
+        gl.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(&shape_buffer));
+        gl.buffer_data_with_array_buffer_view(
+            WebGl2RenderingContext::ARRAY_BUFFER,
+            &float32_array_view(&ANT_SHAPE),
+            WebGl2RenderingContext::STATIC_DRAW,
+        );
+        gl.vertex_attrib_pointer_with_i32(
+            a_vertex_location as u32,
+            3,
+            WebGl2RenderingContext::FLOAT,
+            false,
+            0,
+            0,
+        );
+        gl.enable_vertex_attrib_array(a_vertex_location as u32);
+
+        // Everything below is per ant rather than per vertex.
+        let instance_buffer = |location: i32, size: i32| -> Result<WebGlBuffer, JsValue> {
+            let buffer = gl.create_buffer().ok_or("Failed to create buffer")?;
+            gl.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(&buffer));
+            gl.vertex_attrib_pointer_with_i32(
+                location as u32,
+                size,
+                WebGl2RenderingContext::FLOAT,
+                false,
+                0,
+                0,
+            );
+            gl.enable_vertex_attrib_array(location as u32);
+            gl.vertex_attrib_divisor(location as u32, 1);
+            Ok(buffer)
+        };
+        let position_buffer = instance_buffer(a_position_location, 2)?;
+        let dir_buffer = instance_buffer(a_dir_location, 1)?;
+        let kind_buffer = instance_buffer(a_kind_location, 3)?;
+
+        Ok(AntRenderer {
+            program,
+            u_resolution_location,
+            u_ant_size_location,
+            u_caste_colors_location,
+            u_colony_colors_location,
+            u_food_color_location,
+            position_buffer,
+            dir_buffer,
+            kind_buffer,
+            kinds: RefCell::new(Vec::new()),
+            vao,
+        })
+    }
+
+    pub fn render(&self, gl: &WebGl2RenderingContext, simulation: &Simulation) {
+        gl.use_program(Some(&self.program));
+        gl.bind_vertex_array(Some(&self.vao));
+
+        gl.uniform4fv_with_f32_array(self.u_caste_colors_location.as_ref(), CASTE_COLORS);
+        gl.uniform4fv_with_f32_array(self.u_colony_colors_location.as_ref(), COLONY_COLORS);
+        gl.uniform4fv_with_f32_array(self.u_food_color_location.as_ref(), CARRIED_FOOD_COLOR);
+        gl.uniform2f(
+            self.u_resolution_location.as_ref(),
+            simulation.width,
+            simulation.height,
+        );
+        gl.uniform1f(self.u_ant_size_location.as_ref(), ANT_SIZE);
+
+        gl.bind_buffer(
+            WebGl2RenderingContext::ARRAY_BUFFER,
+            Some(&self.position_buffer),
+        );
+        gl.buffer_data_with_array_buffer_view(
+            WebGl2RenderingContext::ARRAY_BUFFER,
+            &float32_array_view(&simulation.ants),
+            WebGl2RenderingContext::DYNAMIC_DRAW,
+        );
+
+        gl.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(&self.dir_buffer));
+        gl.buffer_data_with_array_buffer_view(
+            WebGl2RenderingContext::ARRAY_BUFFER,
+            &float32_array_view(&simulation.dirs),
+            WebGl2RenderingContext::DYNAMIC_DRAW,
+        );
+
+        // Colony, caste and whether each ant carries food.
+        let mut kinds = self.kinds.borrow_mut();
+        kinds.clear();
+        for ((&colony, &caste), &has_food) in simulation
+            .ant_colonies
+            .iter()
+            .zip(&simulation.castes)
+            .zip(&simulation.has_food)
+        {
+            kinds.extend([colony as f32, caste as usize as f32, has_food as u8 as f32]);
+        }
+        gl.bind_buffer(
+            WebGl2RenderingContext::ARRAY_BUFFER,
+            Some(&self.kind_buffer),
+        );
+        gl.buffer_data_with_array_buffer_view(
+            WebGl2RenderingContext::ARRAY_BUFFER,
+            &float32_array_view(&kinds),
+            WebGl2RenderingContext::DYNAMIC_DRAW,
+        );
+
+        draw_instanced_triangles(
+            gl,
+            (ANT_SHAPE.len() / 3) as i32,
+            simulation.dirs.len() as i32,
+        );
+    }
+}
@@ -0,0 +1,311 @@
+//! Runs the simulation without a browser and writes periodic metrics.
+//!
+//! ```text
+//! headless [--config FILE] [--map FILE] [--steps N] [--every N] [--seed N]
+//!          [--width PX] [--height PX] [--format csv|json] [--output FILE]
+//!          [--sweep FIELD=VALUES]... [--seeds N] [--threads N]
+//! ```
+//!
+//! Configs are read as TOML when the file ends in `.toml` and as JSON
+//! otherwise. Maps are read as PNG when the file ends in `.png` and as ASCII
+//! otherwise, and set the world size unless `--width`/`--height` are given.
+//! JSON output has one sample object per line.
+//!
+//! Passing `--sweep` switches to a parameter sweep, see `sweep.rs`.
+
+use ants::config::SimConfig;
+use ants::consts::GRID_SIZE;
+use ants::map::Map;
+use ants::pheromones::PheromoneChannel;
+use ants::simulation::Simulation;
+use serde::Serialize;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use sweep::Parameter;
+
+mod sweep;
+
+const USAGE: &str = "usage: headless [--config FILE] [--map FILE] [--steps N] [--every N] \
+[--seed N] [--width PX] [--height PX] [--format csv|json] [--output FILE] \
+[--sweep FIELD=VALUES]... [--seeds N] [--threads N]";
+
+/// Pheromone strength above which a cell counts towards the active trails.
+const TRAIL_THRESHOLD: f32 = 0.01;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Csv,
+    Json,
+}
+
+struct Options {
+    config: Option<String>,
+    map: Option<String>,
+    steps: u64,
+    every: u64,
+    seed: Option<u64>,
+    width: Option<f32>,
+    height: Option<f32>,
+    format: Format,
+    output: Option<String>,
+    sweep: Vec<Parameter>,
+    seeds: u64,
+    threads: Option<usize>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            config: None,
+            map: None,
+            steps: 10_000,
+            every: 100,
+            seed: None,
+            width: None,
+            height: None,
+            format: Format::Csv,
+            output: None,
+            sweep: Vec::new(),
+            seeds: 1,
+            threads: None,
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+struct Sample {
+    step: u64,
+    food_delivered: u32,
+    food_stored: f32,
+    trips: u32,
+    average_trip_steps: f64,
+    carrying: usize,
+    searching: usize,
+    food_trail_cells: usize,
+    home_trail_cells: usize,
+}
+
+impl Sample {
+    const CSV_HEADER: &'static str = "step,food_delivered,food_stored,trips,average_trip_steps,\
+carrying,searching,food_trail_cells,home_trail_cells";
+
+    fn take(simulation: &Simulation) -> Sample {
+        let stats = simulation.stats();
+        let carrying = simulation
+            .has_food
+            .iter()
+            .filter(|&&has_food| has_food)
+            .count();
+        let trail_cells = |channel| {
+            (0..simulation.colonies.len())
+                .map(|colony| {
+                    simulation
+                        .pheromones
+                        .layer(colony, channel)
+                        .iter()
+                        .filter(|&&strength| strength > TRAIL_THRESHOLD)
+                        .count()
+                })
+                .sum()
+        };
+        Sample {
+            step: simulation.steps,
+            food_delivered: stats.food_delivered,
+            food_stored: stats.food_stored,
+            trips: stats.trips,
+            average_trip_steps: stats.average_trip_steps(),
+            carrying,
+            searching: simulation.has_food.len() - carrying,
+            food_trail_cells: trail_cells(PheromoneChannel::Food),
+            home_trail_cells: trail_cells(PheromoneChannel::Home),
+        }
+    }
+
+    fn to_csv(&self) -> String {
+        format!(
+            "{},{},{},{},{},{},{},{},{}",
+            self.step,
+            self.food_delivered,
+            self.food_stored,
+            self.trips,
+            self.average_trip_steps,
+            self.carrying,
+            self.searching,
+            self.food_trail_cells,
+            self.home_trail_cells
+        )
+    }
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = args.into_iter();
+    while let Some(flag) = args.next() {
+        if flag == "--help" || flag == "-h" {
+            return Err(USAGE.to_string());
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| format!("Missing value for {}\n{}", flag, USAGE))?;
+        let number = |value: &str| -> Result<u64, String> {
+            value
+                .parse()
+                .map_err(|_| format!("Invalid number for {}: {}", flag, value))
+        };
+        match flag.as_str() {
+            "--config" => options.config = Some(value),
+            "--map" => options.map = Some(value),
+            "--steps" => options.steps = number(&value)?,
+            "--every" => options.every = number(&value)?.max(1),
+            "--seed" => options.seed = Some(number(&value)?),
+            "--width" => options.width = Some(number(&value)? as f32),
+            "--height" => options.height = Some(number(&value)? as f32),
+            "--format" => {
+                options.format = match value.as_str() {
+                    "csv" => Format::Csv,
+                    "json" => Format::Json,
+                    _ => return Err(format!("Unknown format {}, expected csv or json", value)),
+                }
+            }
+            "--output" => options.output = Some(value),
+            "--sweep" => options.sweep.push(Parameter::parse(&value)?),
+            "--seeds" => options.seeds = number(&value)?.max(1),
+            "--threads" => options.threads = Some(number(&value)?.max(1) as usize),
+            _ => return Err(format!("Unknown option {}\n{}", flag, USAGE)),
+        }
+    }
+    Ok(options)
+}
+
+fn has_extension(path: &str, extension: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(extension))
+}
+
+fn load_config(path: &str) -> Result<SimConfig, String> {
+    let source = fs::read_to_string(path).map_err(|e| format!("Reading {}: {}", path, e))?;
+    if has_extension(path, "toml") {
+        SimConfig::from_toml(&source)
+    } else {
+        SimConfig::from_json(&source)
+    }
+}
+
+fn load_map(path: &str) -> Result<Map, String> {
+    let map = if has_extension(path, "png") {
+        let bytes = fs::read(path).map_err(|e| format!("Reading {}: {}", path, e))?;
+        Map::parse_png(&bytes)
+    } else {
+        let source = fs::read_to_string(path).map_err(|e| format!("Reading {}: {}", path, e))?;
+        Map::parse_ascii(&source)
+    };
+    map.map_err(|e| format!("{}: {}", path, e))
+}
+
+/// The starting point shared by every run: the base config, map and world
+/// size.
+struct Setup {
+    config: SimConfig,
+    map: Option<Map>,
+    width: f32,
+    height: f32,
+}
+
+impl Setup {
+    fn from_options(options: &Options) -> Result<Setup, String> {
+        let mut config = match &options.config {
+            Some(path) => load_config(path)?,
+            None => SimConfig::default(),
+        };
+        if let Some(seed) = options.seed {
+            config.seed = seed;
+        }
+        let map = options.map.as_deref().map(load_map).transpose()?;
+        let (map_width, map_height) = map.as_ref().map_or((1200.0, 860.0), |map| {
+            (map.columns as f32 * GRID_SIZE, map.rows as f32 * GRID_SIZE)
+        });
+        let width = options.width.unwrap_or(map_width);
+        let height = options.height.unwrap_or(map_height);
+        let setup = Setup {
+            config,
+            map,
+            width,
+            height,
+        };
+        // Reports worlds and maps the library rejects before any run starts.
+        setup.simulation(setup.config)?;
+        Ok(setup)
+    }
+
+    fn simulation(&self, config: SimConfig) -> Result<Simulation, String> {
+        let mut simulation = Simulation::new(self.width, self.height, config)?;
+        if let Some(map) = &self.map {
+            simulation.load_map(map).map_err(|e| e.to_string())?;
+        }
+        Ok(simulation)
+    }
+}
+
+fn open_output(path: Option<&str>) -> Result<BufWriter<Box<dyn Write>>, String> {
+    let output: Box<dyn Write> = match path {
+        Some(path) => {
+            Box::new(fs::File::create(path).map_err(|e| format!("Creating {}: {}", path, e))?)
+        }
+        None => Box::new(io::stdout()),
+    };
+    Ok(BufWriter::new(output))
+}
+
+fn write_error(e: io::Error) -> String {
+    format!("Writing metrics: {}", e)
+}
+
+fn run(options: Options) -> Result<(), String> {
+    let setup = Setup::from_options(&options)?;
+    let mut output = open_output(options.output.as_deref())?;
+    if !options.sweep.is_empty() {
+        let threads = options.threads.unwrap_or_else(|| {
+            std::thread::available_parallelism().map_or(1, |threads| threads.get())
+        });
+        sweep::run(
+            &setup,
+            &options.sweep,
+            options.steps,
+            options.seeds,
+            threads,
+            options.format,
+            &mut output,
+        )?;
+        return output.flush().map_err(write_error);
+    }
+
+    let mut simulation = setup.simulation(setup.config)?;
+    if options.format == Format::Csv {
+        writeln!(output, "{}", Sample::CSV_HEADER).map_err(write_error)?;
+    }
+    for step in 0..=options.steps {
+        if step > 0 {
+            simulation.step();
+        }
+        if step % options.every != 0 && step != options.steps {
+            continue;
+        }
+        let sample = Sample::take(&simulation);
+        let line = match options.format {
+            Format::Csv => sample.to_csv(),
+            Format::Json => serde_json::to_string(&sample).expect("Error serializing sample"),
+        };
+        writeln!(output, "{}", line).map_err(write_error)?;
+    }
+    output.flush().map_err(write_error)
+}
+
+fn main() {
+    let result = parse_args(std::env::args().skip(1)).and_then(run);
+    if let Err(message) = result {
+        eprintln!("{}", message);
+        std::process::exit(1);
+    }
+}
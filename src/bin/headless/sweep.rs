@@ -0,0 +1,300 @@
+//! Parameter sweeps. Every combination of the swept config fields is run once
+//! per seed across worker threads, and the final samples of each combination
+//! are reduced to a mean and standard deviation per outcome.
+//!
+//! Fields are named as in the config file, with dots reaching into nested
+//! tables such as `scout.speed`. Values are either a list,
+//! `walk_speed=1,2,3`, or an inclusive range, `view_radius=10:30:5`. Seeds
+//! count up from the configured seed and are set with `--seeds` rather than
+//! swept.
+
+use crate::{write_error, Format, Sample, Setup};
+use ants::config::SimConfig;
+use serde_json::{json, Value};
+use std::io::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+/// Reads one outcome from a run's final sample.
+type Measure = fn(&Sample) -> f64;
+
+const OUTCOMES: [(&str, Measure); 5] = [
+    ("food_delivered", |sample| sample.food_delivered as f64),
+    ("trips", |sample| sample.trips as f64),
+    ("average_trip_steps", |sample| sample.average_trip_steps),
+    ("food_trail_cells", |sample| sample.food_trail_cells as f64),
+    ("home_trail_cells", |sample| sample.home_trail_cells as f64),
+];
+
+/// A swept config field and the values it takes.
+pub struct Parameter {
+    field: String,
+    values: Vec<Value>,
+}
+
+impl Parameter {
+    pub fn parse(spec: &str) -> Result<Parameter, String> {
+        let (field, values) = spec
+            .split_once('=')
+            .ok_or_else(|| format!("Invalid sweep {}, expected FIELD=VALUES", spec))?;
+        if field == "seed" {
+            return Err(String::from(
+                "The seed can't be swept, use --seeds N to run N seeds from --seed",
+            ));
+        }
+        let parts: Vec<&str> = values.split(':').collect();
+        let values = if let [start, end, step] = parts[..] {
+            let number = |value: &str| {
+                value
+                    .parse::<f64>()
+                    .map_err(|_| format!("Invalid number in sweep {}: {}", spec, value))
+            };
+            let (start, end, step) = (number(start)?, number(end)?, number(step)?);
+            if step <= 0.0 || end < start {
+                return Err(format!("Invalid range in sweep {}", spec));
+            }
+            let count = ((end - start) / step + 1e-9).floor() as usize + 1;
+            (0..count)
+                .map(|i| json_number(start + i as f64 * step))
+                .collect()
+        } else {
+            values.split(',').map(parse_value).collect()
+        };
+        Ok(Parameter {
+            field: field.to_string(),
+            values,
+        })
+    }
+}
+
+/// Whole numbers become JSON integers so they can fill integer fields too.
+fn json_number(value: f64) -> Value {
+    if value.fract() == 0.0 && value.abs() < i64::MAX as f64 {
+        Value::from(value as i64)
+    } else {
+        Value::from(value)
+    }
+}
+
+fn parse_value(value: &str) -> Value {
+    value
+        .parse::<f64>()
+        .map_or_else(|_| Value::from(value), json_number)
+}
+
+fn display_value(value: &Value) -> String {
+    match value {
+        Value::String(value) => value.clone(),
+        value => value.to_string(),
+    }
+}
+
+fn combinations(parameters: &[Parameter]) -> Vec<Vec<Value>> {
+    let mut combinations = vec![Vec::new()];
+    for parameter in parameters {
+        combinations = combinations
+            .into_iter()
+            .flat_map(|combination| {
+                parameter.values.iter().map(move |value| {
+                    let mut combination = combination.clone();
+                    combination.push(value.clone());
+                    combination
+                })
+            })
+            .collect();
+    }
+    combinations
+}
+
+fn configure(
+    base: &SimConfig,
+    parameters: &[Parameter],
+    values: &[Value],
+    seed: u64,
+) -> Result<SimConfig, String> {
+    let mut config = serde_json::to_value(base).expect("Error serializing config");
+    for (parameter, value) in parameters.iter().zip(values) {
+        let pointer = format!("/{}", parameter.field.replace('.', "/"));
+        let field = config
+            .pointer_mut(&pointer)
+            .ok_or_else(|| format!("Unknown config field {}", parameter.field))?;
+        *field = value.clone();
+    }
+    config["seed"] = Value::from(seed);
+    let config: SimConfig =
+        serde_json::from_value(config).map_err(|e| format!("Invalid sweep value: {}", e))?;
+    config.validate()?;
+    Ok(config)
+}
+
+fn mean_and_std(values: &[f64]) -> (f64, f64) {
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    let variance = values
+        .iter()
+        .map(|value| (value - mean).powi(2))
+        .sum::<f64>()
+        / values.len() as f64;
+    (mean, variance.sqrt())
+}
+
+pub fn run(
+    setup: &Setup,
+    parameters: &[Parameter],
+    steps: u64,
+    seeds: u64,
+    threads: usize,
+    format: Format,
+    output: &mut impl Write,
+) -> Result<(), String> {
+    let combinations = combinations(parameters);
+    let mut jobs = Vec::new();
+    for values in &combinations {
+        for seed in setup.config.seed..setup.config.seed + seeds {
+            jobs.push(configure(&setup.config, parameters, values, seed)?);
+        }
+    }
+
+    let next_job = AtomicUsize::new(0);
+    let finished = AtomicUsize::new(0);
+    let samples = Mutex::new(vec![None; jobs.len()]);
+    thread::scope(|scope| {
+        for _ in 0..threads.min(jobs.len()) {
+            scope.spawn(|| loop {
+                let idx = next_job.fetch_add(1, Ordering::Relaxed);
+                let Some(config) = jobs.get(idx) else {
+                    break;
+                };
+                let sample = setup.simulation(*config).map(|mut simulation| {
+                    for _ in 0..steps {
+                        simulation.step();
+                    }
+                    Sample::take(&simulation)
+                });
+                samples.lock().unwrap()[idx] = Some(sample);
+                let done = finished.fetch_add(1, Ordering::Relaxed) + 1;
+                eprintln!("Finished run {}/{}", done, jobs.len());
+            });
+        }
+    });
+    let samples = samples
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|sample| sample.expect("Every job runs"))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if format == Format::Csv {
+        let mut header: Vec<String> = parameters.iter().map(|p| p.field.clone()).collect();
+        header.push(String::from("runs"));
+        for (outcome, _) in OUTCOMES {
+            header.push(format!("{}_mean", outcome));
+            header.push(format!("{}_std", outcome));
+        }
+        writeln!(output, "{}", header.join(",")).map_err(write_error)?;
+    }
+    for (values, runs) in combinations.iter().zip(samples.chunks(seeds as usize)) {
+        let summaries = OUTCOMES.map(|(outcome, measure)| {
+            let measured: Vec<f64> = runs.iter().map(measure).collect();
+            (outcome, mean_and_std(&measured))
+        });
+        let line = match format {
+            Format::Csv => {
+                let mut row: Vec<String> = values.iter().map(display_value).collect();
+                row.push(runs.len().to_string());
+                for (_, (mean, std)) in summaries {
+                    row.push(mean.to_string());
+                    row.push(std.to_string());
+                }
+                row.join(",")
+            }
+            Format::Json => {
+                let mut record = serde_json::Map::new();
+                let params = parameters
+                    .iter()
+                    .zip(values)
+                    .map(|(parameter, value)| (parameter.field.clone(), value.clone()))
+                    .collect();
+                record.insert(String::from("params"), Value::Object(params));
+                record.insert(String::from("runs"), Value::from(runs.len()));
+                for (outcome, (mean, std)) in summaries {
+                    record.insert(outcome.to_string(), json!({ "mean": mean, "std": std }));
+                }
+                Value::Object(record).to_string()
+            }
+        };
+        writeln!(output, "{}", line).map_err(write_error)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(spec: &str) -> Vec<Value> {
+        Parameter::parse(spec).unwrap().values
+    }
+
+    #[test]
+    fn parses_lists_and_ranges() {
+        assert_eq!(
+            values("walk_speed=1,2.5,x"),
+            [json!(1), json!(2.5), json!("x")]
+        );
+        assert_eq!(
+            values("view_radius=10:30:5"),
+            [json!(10), json!(15), json!(20), json!(25), json!(30)]
+        );
+        assert_eq!(
+            values("walk_speed=0:1:0.5"),
+            [json!(0), json!(0.5), json!(1)]
+        );
+        assert_eq!(values("walk_speed=0:0.9:0.5"), [json!(0), json!(0.5)]);
+        assert_eq!(values("walk_speed=2:2:1"), [json!(2)]);
+    }
+
+    #[test]
+    fn rejects_bad_specs() {
+        for spec in [
+            "walk_speed",
+            "walk_speed=3:1:1",
+            "walk_speed=1:3:0",
+            "walk_speed=1:x:1",
+            "seed=1,2",
+        ] {
+            assert!(Parameter::parse(spec).is_err(), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn combines_every_value() {
+        let parameters = [
+            Parameter::parse("a=1,2").unwrap(),
+            Parameter::parse("b=x,y,z").unwrap(),
+        ];
+        let all = combinations(&parameters);
+        assert_eq!(all.len(), 6);
+        assert_eq!(all[0], [json!(1), json!("x")]);
+        assert_eq!(all[5], [json!(2), json!("z")]);
+        assert_eq!(combinations(&[]), [Vec::<Value>::new()]);
+    }
+
+    #[test]
+    fn configures_nested_fields_and_the_seed() {
+        let parameters = [
+            Parameter::parse("scout.speed=2").unwrap(),
+            Parameter::parse("walk_speed=3").unwrap(),
+        ];
+        let config =
+            configure(&SimConfig::default(), &parameters, &[json!(2), json!(3)], 7).unwrap();
+        assert_eq!(config.scout.speed, 2.0);
+        assert_eq!(config.walk_speed, 3.0);
+        assert_eq!(config.seed, 7);
+
+        for field in ["scout.pace", "nonsense"] {
+            let parameters = [Parameter::parse(&format!("{}=1", field)).unwrap()];
+            assert!(configure(&SimConfig::default(), &parameters, &[json!(1)], 0).is_err());
+        }
+    }
+}
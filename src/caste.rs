@@ -0,0 +1,104 @@
+use crate::config::SimConfig;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+/// Scouts wander widely and mostly ignore trails, foragers follow trails
+/// closely and soldiers patrol slowly near where they are.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Caste {
+    Scout,
+    Forager,
+    Soldier,
+}
+
+impl Caste {
+    pub const ALL: [Caste; 3] = [Caste::Scout, Caste::Forager, Caste::Soldier];
+
+    /// Draws a caste with probability proportional to the config's ratios.
+    pub fn pick(config: &SimConfig, rng: &mut impl Rng) -> Caste {
+        let weights = Caste::ALL.map(|caste| config.caste_ratio(caste).max(0.0));
+        let total: f32 = weights.iter().sum();
+        let mut choice = rng.gen::<f32>() * total;
+        for (caste, weight) in Caste::ALL.into_iter().zip(weights) {
+            if choice < weight {
+                return caste;
+            }
+            choice -= weight;
+        }
+        Caste::Forager
+    }
+}
+
+/// Multipliers applied to the base config for ants of one caste. `1.0`
+/// everywhere behaves exactly like the base config.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CasteParams {
+    pub wander: f32,
+    pub speed: f32,
+    /// Scales how hard the ant steers towards pheromone.
+    pub sensitivity: f32,
+    pub deposit: f32,
+}
+
+impl Default for CasteParams {
+    fn default() -> Self {
+        CasteParams {
+            wander: 1.0,
+            speed: 1.0,
+            sensitivity: 1.0,
+            deposit: 1.0,
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl CasteParams {
+    #[wasm_bindgen(constructor)]
+    pub fn new(wander: f32, speed: f32, sensitivity: f32, deposit: f32) -> CasteParams {
+        CasteParams {
+            wander,
+            speed,
+            sensitivity,
+            deposit,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256Plus;
+
+    fn picks(scout: f32, forager: f32, soldier: f32) -> Vec<Caste> {
+        let config = SimConfig {
+            scout_ratio: scout,
+            forager_ratio: forager,
+            soldier_ratio: soldier,
+            ..SimConfig::default()
+        };
+        let mut rng = Xoshiro256Plus::seed_from_u64(1);
+        (0..1000).map(|_| Caste::pick(&config, &mut rng)).collect()
+    }
+
+    #[test]
+    fn single_ratios_pick_only_that_caste() {
+        assert!(picks(1.0, 0.0, 0.0).iter().all(|&c| c == Caste::Scout));
+        assert!(picks(0.0, 1.0, 0.0).iter().all(|&c| c == Caste::Forager));
+        assert!(picks(0.0, 0.0, 1.0).iter().all(|&c| c == Caste::Soldier));
+        assert!(picks(0.0, 0.0, 0.0).iter().all(|&c| c == Caste::Forager));
+    }
+
+    #[test]
+    fn ratios_set_the_mix() {
+        let castes = picks(1.0, 0.0, 3.0);
+        let scouts = castes.iter().filter(|&&c| c == Caste::Scout).count();
+        assert!((200..300).contains(&scouts), "{} scouts", scouts);
+        assert!(!castes.contains(&Caste::Forager));
+    }
+}
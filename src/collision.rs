@@ -0,0 +1,134 @@
+/// Distance a hit position is pulled back along the segment, so that it lies
+/// strictly inside the cell before the one it ran into.
+const HIT_OFFSET: f32 = 1e-3;
+
+/// Where a segment first enters a blocked grid cell.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hit {
+    pub cell: (isize, isize),
+    /// Just short of the face the segment crosses into `cell`.
+    pub pos: (f32, f32),
+    /// Unit normal of the crossed face, pointing back out of `cell`.
+    pub normal: (f32, f32),
+    /// Fraction of the segment travelled before the hit.
+    pub t: f32,
+}
+
+/// Walks the cells of a `cell_size` grid crossed by the segment `from -> to`,
+/// in order and without skipping diagonal neighbours, and returns the first
+/// one `blocked` rejects. The cell `from` lies in is never checked.
+pub fn sweep(
+    from: (f32, f32),
+    to: (f32, f32),
+    cell_size: f32,
+    mut blocked: impl FnMut((isize, isize)) -> bool,
+) -> Option<Hit> {
+    let cell_of = |pos: (f32, f32)| {
+        (
+            (pos.0 / cell_size).floor() as isize,
+            (pos.1 / cell_size).floor() as isize,
+        )
+    };
+    let delta = (to.0 - from.0, to.1 - from.1);
+    let mut cell = cell_of(from);
+    let end = cell_of(to);
+    let step = (delta.0.signum() as isize, delta.1.signum() as isize);
+    // Segment fraction at which the next vertical / horizontal face is crossed.
+    let first_crossing = |pos: f32, cell: isize, delta: f32| {
+        if delta > 0.0 {
+            ((cell + 1) as f32 * cell_size - pos) / delta
+        } else if delta < 0.0 {
+            (cell as f32 * cell_size - pos) / delta
+        } else {
+            f32::INFINITY
+        }
+    };
+    let mut t_max = (
+        first_crossing(from.0, cell.0, delta.0),
+        first_crossing(from.1, cell.1, delta.1),
+    );
+    let t_delta = (cell_size / delta.0.abs(), cell_size / delta.1.abs());
+
+    while cell != end {
+        let (t, normal) = if t_max.0 < t_max.1 {
+            cell.0 += step.0;
+            t_max.0 += t_delta.0;
+            (t_max.0 - t_delta.0, (-step.0 as f32, 0.0))
+        } else {
+            cell.1 += step.1;
+            t_max.1 += t_delta.1;
+            (t_max.1 - t_delta.1, (0.0, -step.1 as f32))
+        };
+        if t > 1.0 {
+            break;
+        }
+        if blocked(cell) {
+            let length = (delta.0 * delta.0 + delta.1 * delta.1).sqrt();
+            let back = (t - HIT_OFFSET / length).max(0.0);
+            let pos = (from.0 + delta.0 * back, from.1 + delta.1 * back);
+            return Some(Hit {
+                cell,
+                pos,
+                normal,
+                t,
+            });
+        }
+    }
+    None
+}
+
+/// Mirrors the heading `dir` about a surface with unit `normal`.
+pub fn reflect(dir: f32, normal: (f32, f32)) -> f32 {
+    let (sin, cos) = dir.sin_cos();
+    let dot = cos * normal.0 + sin * normal.1;
+    (sin - 2.0 * dot * normal.1).atan2(cos - 2.0 * dot * normal.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::PI;
+
+    #[test]
+    fn visits_both_cells_around_a_diagonal_corner() {
+        let mut visited = Vec::new();
+        let hit = sweep((5.0, 5.0), (15.0, 15.0), 10.0, |cell| {
+            visited.push(cell);
+            false
+        });
+        assert_eq!(hit, None);
+        assert_eq!(visited.len(), 2);
+        assert_eq!(visited[1], (1, 1));
+    }
+
+    #[test]
+    fn diagonal_walls_block_corner_cutting() {
+        let walls = [(1, 0), (0, 1)];
+        let hit = sweep((8.0, 8.0), (12.0, 12.0), 10.0, |cell| walls.contains(&cell))
+            .expect("Segment passes between two diagonal walls");
+        assert!(walls.contains(&hit.cell));
+        assert!(hit.pos.0 < 10.0 && hit.pos.1 < 10.0);
+    }
+
+    #[test]
+    fn reports_the_face_normal_and_stops_short_of_it() {
+        let hit = sweep((5.0, 5.0), (25.0, 7.0), 10.0, |cell| cell == (2, 0)).unwrap();
+        assert_eq!(hit.cell, (2, 0));
+        assert_eq!(hit.normal, (-1.0, 0.0));
+        assert!(hit.pos.0 < 20.0 && hit.pos.0 > 19.9);
+        assert!((hit.t - 0.75).abs() < 1e-6);
+    }
+
+    #[test]
+    fn ignores_the_starting_cell() {
+        assert_eq!(sweep((5.0, 5.0), (6.0, 5.0), 10.0, |_| true), None);
+    }
+
+    #[test]
+    fn reflects_about_the_normal() {
+        let dir = reflect(PI / 4.0, (-1.0, 0.0));
+        assert!((dir - 3.0 * PI / 4.0).abs() < 1e-5);
+        let dir = reflect(0.0, (-1.0, 0.0));
+        assert!((dir.abs() - PI).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,30 @@
+use crate::consts::COLONY_COLORS;
+use crate::stats::ColonyStats;
+use serde::{Deserialize, Serialize};
+
+/// A nest and the ants that belong to it. Colonies share the grid and its food
+/// but only lay and follow their own pheromone trails.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Colony {
+    pub nest_pos: (f32, f32),
+    pub stats: ColonyStats,
+}
+
+impl Colony {
+    pub fn new(nest_pos: (f32, f32)) -> Self {
+        Colony {
+            nest_pos,
+            stats: ColonyStats::default(),
+        }
+    }
+}
+
+/// Multiplies `color` by the tint of `colony`.
+pub fn tint(color: &[f32; 4], colony: usize) -> [f32; 4] {
+    let offset = (colony % (COLONY_COLORS.len() / 4)) * 4;
+    let mut tinted = *color;
+    for (channel, tint) in tinted.iter_mut().zip(&COLONY_COLORS[offset..offset + 4]) {
+        *channel *= tint;
+    }
+    tinted
+}
@@ -0,0 +1,240 @@
+use crate::caste::{Caste, CasteParams};
+use crate::consts::{
+    ANT_COUNT, ANT_PHEROMONE_TIMER, ANT_STEERING_STRENGTH, ANT_VIEW_ARC, ANT_VIEW_RADIUS,
+    ENERGY_PER_FOOD, FOOD_PER_CELL, FOOD_PHEROMONE_DECAY, FORAGER_RATIO, HOME_PHEROMONE_DECAY,
+    MAX_ENERGY, MAX_TURN_RATE, MIN_PHEROMONE_CELL_SIZE, MOVEMENT_COST, NEST_HONING_STRENGTH,
+    ODOMETRY_DRIFT, ODOMETRY_NOISE, PHEROMONE_CELL_SIZE, PHEROMONE_DEPOSIT,
+    PHEROMONE_DIFFUSION_RATE, SCOUT_DEPOSIT, SCOUT_RATIO, SCOUT_SENSITIVITY, SCOUT_SPEED,
+    SCOUT_WANDER, SEED, SOLDIER_DEPOSIT, SOLDIER_RATIO, SOLDIER_SENSITIVITY, SOLDIER_SPEED,
+    SOLDIER_WANDER, SPAWN_COST, SPAWN_THRESHOLD, WALK_SPEED, WANDER_COEFFICIENT,
+};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+/// How pheromone strength falls off each step. `Linear` subtracts the decay
+/// rate, `Exponential` multiplies by `1 - rate`.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EvaporationKernel {
+    Linear,
+    Exponential,
+}
+
+/// Which neighbouring cells pheromone spreads to each step.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffusionKernel {
+    None,
+    Cross,
+    Box,
+}
+
+/// What the edge of the world does. `Solid` edges are walls, `Wrap` joins
+/// opposite edges into a torus and `Open` removes ants that walk off, counting
+/// them as deaths.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Boundary {
+    Solid,
+    Wrap,
+    Open,
+}
+
+/// How ants carrying food find their way home. `Direct` steers straight at
+/// the nest, `FlowField` follows the shortest walkable path around walls and
+/// `PathIntegration` steers back along each ant's own noisy odometry.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Homing {
+    Direct,
+    FlowField,
+    PathIntegration,
+}
+
+/// Tunable simulation parameters. Missing fields in JSON/TOML input fall back
+/// to the defaults in `consts.rs`.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SimConfig {
+    pub ant_count: usize,
+    pub wander_coefficient: f32,
+    pub walk_speed: f32,
+    pub nest_honing_strength: f32,
+    pub homing: Homing,
+    /// Spread of the random heading error, in radians, added to every step an
+    /// ant's odometry records.
+    pub odometry_noise: f32,
+    /// Constant heading error, in radians, added to every recorded step.
+    pub odometry_drift: f32,
+    pub pheromone_timer: usize,
+    pub view_radius: f32,
+    pub view_arc: f32,
+    pub steering_strength: f32,
+    /// Most an ant's heading can change in one step from steering, homing and
+    /// wandering combined. Bouncing off walls and turning round at food or
+    /// the nest are not limited.
+    pub max_turn_rate: f32,
+    pub food_pheromone_decay: f32,
+    pub home_pheromone_decay: f32,
+    pub pheromone_deposit: f32,
+    pub pheromone_cell_size: f32,
+    pub evaporation: EvaporationKernel,
+    pub diffusion: DiffusionKernel,
+    pub diffusion_rate: f32,
+    pub food_per_cell: f32,
+    pub boundary: Boundary,
+    /// Seeds the simulation's random stream. Runs with the same seed, config
+    /// and map produce identical trajectories.
+    pub seed: u64,
+    /// Whether ants spend energy, die and are born. When off the population
+    /// only changes through `ant_count`.
+    pub lifecycle: bool,
+    pub max_energy: f32,
+    /// Energy spent per pixel walked.
+    pub movement_cost: f32,
+    /// Energy restored per unit of stored food eaten at the nest.
+    pub energy_per_food: f32,
+    /// Stored food above which a colony spawns a new ant each step.
+    pub spawn_threshold: f32,
+    /// Stored food used up by each spawned ant.
+    pub spawn_cost: f32,
+    /// Relative share of new ants that are scouts, foragers and soldiers.
+    pub scout_ratio: f32,
+    pub forager_ratio: f32,
+    pub soldier_ratio: f32,
+    /// Per-caste multipliers. Multipliers left out of a caste table in JSON or
+    /// TOML input are `1.0`.
+    pub scout: CasteParams,
+    pub forager: CasteParams,
+    pub soldier: CasteParams,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        SimConfig {
+            ant_count: ANT_COUNT,
+            wander_coefficient: WANDER_COEFFICIENT,
+            walk_speed: WALK_SPEED,
+            nest_honing_strength: NEST_HONING_STRENGTH,
+            homing: Homing::Direct,
+            odometry_noise: ODOMETRY_NOISE,
+            odometry_drift: ODOMETRY_DRIFT,
+            pheromone_timer: ANT_PHEROMONE_TIMER,
+            view_radius: ANT_VIEW_RADIUS,
+            view_arc: ANT_VIEW_ARC,
+            steering_strength: ANT_STEERING_STRENGTH,
+            max_turn_rate: MAX_TURN_RATE,
+            food_pheromone_decay: FOOD_PHEROMONE_DECAY,
+            home_pheromone_decay: HOME_PHEROMONE_DECAY,
+            pheromone_deposit: PHEROMONE_DEPOSIT,
+            pheromone_cell_size: PHEROMONE_CELL_SIZE,
+            evaporation: EvaporationKernel::Linear,
+            diffusion: DiffusionKernel::Cross,
+            diffusion_rate: PHEROMONE_DIFFUSION_RATE,
+            food_per_cell: FOOD_PER_CELL,
+            boundary: Boundary::Solid,
+            seed: SEED,
+            lifecycle: false,
+            max_energy: MAX_ENERGY,
+            movement_cost: MOVEMENT_COST,
+            energy_per_food: ENERGY_PER_FOOD,
+            spawn_threshold: SPAWN_THRESHOLD,
+            spawn_cost: SPAWN_COST,
+            scout_ratio: SCOUT_RATIO,
+            forager_ratio: FORAGER_RATIO,
+            soldier_ratio: SOLDIER_RATIO,
+            scout: CasteParams {
+                wander: SCOUT_WANDER,
+                speed: SCOUT_SPEED,
+                sensitivity: SCOUT_SENSITIVITY,
+                deposit: SCOUT_DEPOSIT,
+            },
+            forager: CasteParams::default(),
+            soldier: CasteParams {
+                wander: SOLDIER_WANDER,
+                speed: SOLDIER_SPEED,
+                sensitivity: SOLDIER_SENSITIVITY,
+                deposit: SOLDIER_DEPOSIT,
+            },
+        }
+    }
+}
+
+impl SimConfig {
+    /// Rejects values the simulation can't run with.
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.pheromone_cell_size.is_finite()
+            || self.pheromone_cell_size < MIN_PHEROMONE_CELL_SIZE
+        {
+            return Err(format!(
+                "pheromone_cell_size must be at least {}, got {}",
+                MIN_PHEROMONE_CELL_SIZE, self.pheromone_cell_size
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn caste_ratio(&self, caste: Caste) -> f32 {
+        match caste {
+            Caste::Scout => self.scout_ratio,
+            Caste::Forager => self.forager_ratio,
+            Caste::Soldier => self.soldier_ratio,
+        }
+    }
+
+    pub fn caste_params(&self, caste: Caste) -> CasteParams {
+        match caste {
+            Caste::Scout => self.scout,
+            Caste::Forager => self.forager,
+            Caste::Soldier => self.soldier,
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl SimConfig {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> SimConfig {
+        SimConfig::default()
+    }
+
+    pub fn from_json(json: &str) -> Result<SimConfig, String> {
+        let config: SimConfig =
+            serde_json::from_str(json).map_err(|e| format!("Invalid config JSON: {}", e))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_toml(toml: &str) -> Result<SimConfig, String> {
+        let config: SimConfig =
+            toml::from_str(toml).map_err(|e| format!("Invalid config TOML: {}", e))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Error serializing config")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_tiny_pheromone_cells() {
+        for size in ["0.0", "-2.0", "1e-6"] {
+            let json = format!("{{\"pheromone_cell_size\": {}}}", size);
+            assert!(SimConfig::from_json(&json).is_err());
+            let toml = format!("pheromone_cell_size = {}", size);
+            assert!(SimConfig::from_toml(&toml).is_err());
+        }
+        assert!(SimConfig::from_toml("pheromone_cell_size = inf").is_err());
+        assert!(SimConfig::from_json("{\"pheromone_cell_size\": 4.0}").is_ok());
+    }
+}
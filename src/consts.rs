@@ -1,19 +1,69 @@
-pub const PI: f32 = std::f32::consts::PI;
-
-pub const ANT_SIZE: f32 = 2.0;
-pub const ANT_COLOR: &[f32; 4] = &[0.7, 0.7, 0.7, 1.0];
-pub const ANT_COUNT: usize = 1000;
-pub const WANDER_COEFFICIENT: f32 = 0.1;
-pub const WALK_SPEED: f32 = 2.0;
-pub const NEST_HONING_STRENGTH: f32 = 1.0;
-pub const ANT_PHEROMONE_TIMER: usize = 60;
-pub const ANT_VIEW_RADIUS: f32 = 20.0;
-pub const ANT_VIEW_ARC: f32 = PI / 1.2;
-
-pub const GRID_SIZE: f32 = 10.0;
-pub const GRID_COLORS: &[f32; 16] = &[
-    0.0, 0.0, 0.0, 0.0, 1.0, 0.5, 1.0, 1.0, 0.5, 1.0, 0.5, 1.0, 0.2, 0.2, 0.2, 1.0,
-];
-
-pub const PHEROMONE_SIZE: f32 = 2.0;
-pub const PHEROMONE_COLOR: &[f32; 4] = &[0.5, 1.0, 0.5, 1.0];
+pub const PI: f32 = std::f32::consts::PI;
+
+/// Half the length of an ant's body, in pixels.
+pub const ANT_SIZE: f32 = 3.0;
+/// Colour of the food dot carried in front of an ant.
+pub const CARRIED_FOOD_COLOR: &[f32; 4] = &[0.5, 1.0, 0.5, 1.0];
+/// Ant colours for scouts, foragers and soldiers.
+pub const CASTE_COLORS: &[f32; 12] = &[
+    0.55, 0.8, 1.0, 1.0, 0.7, 0.7, 0.7, 1.0, 1.0, 0.55, 0.35, 1.0,
+];
+pub const ANT_COUNT: usize = 1000;
+pub const WANDER_COEFFICIENT: f32 = 0.1;
+pub const WALK_SPEED: f32 = 2.0;
+pub const NEST_HONING_STRENGTH: f32 = 1.0;
+pub const ANT_PHEROMONE_TIMER: usize = 60;
+pub const ANT_VIEW_RADIUS: f32 = 20.0;
+pub const ANT_VIEW_ARC: f32 = PI / 1.2;
+pub const FOOD_PHEROMONE_DECAY: f32 = 0.003;
+pub const HOME_PHEROMONE_DECAY: f32 = 0.003;
+pub const PHEROMONE_DEPOSIT: f32 = 1.0;
+pub const PHEROMONE_DIFFUSION_RATE: f32 = 0.05;
+pub const ANT_STEERING_STRENGTH: f32 = PI / 8.0;
+pub const MAX_TURN_RATE: f32 = PI / 4.0;
+pub const SEED: u64 = 0;
+pub const ODOMETRY_NOISE: f32 = 0.1;
+pub const ODOMETRY_DRIFT: f32 = 0.0;
+/// Wall reflections followed within one step before an ant stops.
+pub const MAX_BOUNCES: usize = 3;
+
+pub const MAX_ENERGY: f32 = 1000.0;
+pub const MOVEMENT_COST: f32 = 0.25;
+pub const ENERGY_PER_FOOD: f32 = 500.0;
+pub const SPAWN_THRESHOLD: f32 = 20.0;
+pub const SPAWN_COST: f32 = 5.0;
+
+pub const SCOUT_RATIO: f32 = 0.0;
+pub const FORAGER_RATIO: f32 = 1.0;
+pub const SOLDIER_RATIO: f32 = 0.0;
+
+/// Scouts roam widely and quickly, paying little attention to trails.
+pub const SCOUT_WANDER: f32 = 3.0;
+pub const SCOUT_SPEED: f32 = 1.2;
+pub const SCOUT_SENSITIVITY: f32 = 0.2;
+pub const SCOUT_DEPOSIT: f32 = 0.5;
+/// Soldiers move slowly and stick to trails.
+pub const SOLDIER_WANDER: f32 = 0.5;
+pub const SOLDIER_SPEED: f32 = 0.8;
+pub const SOLDIER_SENSITIVITY: f32 = 1.0;
+pub const SOLDIER_DEPOSIT: f32 = 0.5;
+
+pub const GRID_SIZE: f32 = 10.0;
+/// Largest brush radius, in grid cells, that `set_brush` accepts.
+pub const MAX_BRUSH_RADIUS: u32 = 50;
+pub const FOOD_PER_CELL: f32 = 50.0;
+pub const GRID_COLORS: &[f32; 16] = &[
+    0.0, 0.0, 0.0, 0.0, 1.0, 0.5, 1.0, 1.0, 0.5, 1.0, 0.5, 1.0, 0.2, 0.2, 0.2, 1.0,
+];
+
+pub const PHEROMONE_CELL_SIZE: f32 = 5.0;
+/// Smaller pheromone cells would need more than a cell per pixel.
+pub const MIN_PHEROMONE_CELL_SIZE: f32 = 1.0;
+pub const FOOD_PHEROMONE_COLOR: &[f32; 4] = &[0.5, 1.0, 0.5, 1.0];
+pub const HOME_PHEROMONE_COLOR: &[f32; 4] = &[0.4, 0.6, 1.0, 1.0];
+
+/// Tints applied to each colony's nest, ants and trails, cycled when there
+/// are more colonies than entries. Colony 0 keeps the base colours.
+pub const COLONY_COLORS: &[f32; 16] = &[
+    1.0, 1.0, 1.0, 1.0, 1.0, 0.45, 0.35, 1.0, 0.45, 0.75, 1.0, 1.0, 1.0, 0.9, 0.3, 1.0,
+];
@@ -0,0 +1,207 @@
+use crate::{
+    consts::GRID_SIZE,
+    functions::get_resource_in_cell,
+    grid::{CellCoord, GridResource},
+};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// Path costs in tenths of a cell, so diagonal steps stay integral.
+const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+const UNREACHABLE: u32 = u32::MAX;
+
+const NEIGHBOURS: [(isize, isize); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+/// Walking distance from every grid cell to each colony's nest, around walls
+/// and other colonies' nests. Ants head home by stepping to the neighbouring
+/// cell that is closest to their nest.
+pub struct FlowField {
+    columns: usize,
+    rows: usize,
+    wrap: bool,
+    distances: Vec<Vec<u32>>,
+}
+
+impl FlowField {
+    pub fn new(grid: &[f32], columns: usize, rows: usize, colonies: usize, wrap: bool) -> Self {
+        let mut field = FlowField {
+            columns,
+            rows,
+            wrap,
+            distances: Vec::with_capacity(colonies),
+        };
+        for colony in 0..colonies {
+            let distances = field.distances_to_nest(grid, colony);
+            field.distances.push(distances);
+        }
+        field
+    }
+
+    fn slot(&self, cell: CellCoord) -> usize {
+        cell.row() * self.columns + cell.column()
+    }
+
+    fn neighbour(&self, cell: CellCoord, offset: (isize, isize)) -> Option<CellCoord> {
+        let column = cell.column() as isize + offset.0;
+        let row = cell.row() as isize + offset.1;
+        if self.wrap {
+            Some(CellCoord::wrapped(column, row, self.columns, self.rows))
+        } else {
+            CellCoord::new(column, row, self.columns, self.rows)
+        }
+    }
+
+    /// Dijkstra outwards from the nest cells. Diagonal steps may not cut the
+    /// corner of a blocked cell, matching how ants collide with the grid.
+    fn distances_to_nest(&self, grid: &[f32], colony: usize) -> Vec<u32> {
+        let open = |cell: CellCoord| match get_resource_in_cell(grid, self.columns, cell) {
+            GridResource::Wall => false,
+            GridResource::Nest(nest) => nest == colony,
+            GridResource::Blank | GridResource::Food => true,
+        };
+        let mut distances = vec![UNREACHABLE; self.columns * self.rows];
+        let mut queue = BinaryHeap::new();
+        for row in 0..self.rows {
+            for column in 0..self.columns {
+                let cell = CellCoord::new(column as isize, row as isize, self.columns, self.rows)
+                    .expect("Cell is inside the grid");
+                if get_resource_in_cell(grid, self.columns, cell) == GridResource::Nest(colony) {
+                    distances[self.slot(cell)] = 0;
+                    queue.push(Reverse((0, self.slot(cell))));
+                }
+            }
+        }
+
+        while let Some(Reverse((distance, i))) = queue.pop() {
+            if distance > distances[i] {
+                continue;
+            }
+            let cell = CellCoord::new(
+                (i % self.columns) as isize,
+                (i / self.columns) as isize,
+                self.columns,
+                self.rows,
+            )
+            .expect("Queued cells are inside the grid");
+            for offset in NEIGHBOURS {
+                let Some(next) = self.neighbour(cell, offset) else {
+                    continue;
+                };
+                let diagonal = offset.0 != 0 && offset.1 != 0;
+                let cost = if diagonal {
+                    let sides = [(offset.0, 0), (0, offset.1)];
+                    if sides
+                        .iter()
+                        .any(|&side| !self.neighbour(cell, side).is_some_and(open))
+                    {
+                        continue;
+                    }
+                    DIAGONAL_COST
+                } else {
+                    STRAIGHT_COST
+                };
+                let j = self.slot(next);
+                if open(next) && distance + cost < distances[j] {
+                    distances[j] = distance + cost;
+                    queue.push(Reverse((distance + cost, j)));
+                }
+            }
+        }
+        distances
+    }
+
+    /// Heading from `pos` towards the neighbouring cell closest to `colony`'s
+    /// nest, or `None` if `pos` is off the grid, cut off from the nest or
+    /// already inside it.
+    pub fn direction(&self, colony: usize, pos: (f32, f32)) -> Option<f32> {
+        let distances = self.distances.get(colony)?;
+        let cell = CellCoord::from_pos(pos, self.columns, self.rows)?;
+        let reachable = |offset| {
+            self.neighbour(cell, offset)
+                .is_some_and(|next| distances[self.slot(next)] != UNREACHABLE)
+        };
+        let mut best = (distances[self.slot(cell)], None);
+        for offset in NEIGHBOURS {
+            let Some(next) = self.neighbour(cell, offset) else {
+                continue;
+            };
+            let cuts_corner = offset.0 != 0
+                && offset.1 != 0
+                && !(reachable((offset.0, 0)) && reachable((0, offset.1)));
+            let distance = distances[self.slot(next)];
+            if distance < best.0 && !cuts_corner {
+                best = (distance, Some(offset));
+            }
+        }
+        let offset = best.1?;
+        let target = (
+            (cell.column() as f32 + 0.5 + offset.0 as f32) * GRID_SIZE,
+            (cell.row() as f32 + 0.5 + offset.1 as f32) * GRID_SIZE,
+        );
+        Some((target.1 - pos.1).atan2(target.0 - pos.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::Map;
+
+    fn field(ascii: &str, wrap: bool) -> FlowField {
+        let map = Map::parse_ascii(ascii).unwrap();
+        let grid: Vec<f32> = map
+            .cells
+            .iter()
+            .flat_map(|cell| {
+                let (kind, amount) = cell.cell_values(1.0);
+                [kind, amount]
+            })
+            .collect();
+        FlowField::new(&grid, map.columns, map.rows, 1, wrap)
+    }
+
+    fn cell_center(column: f32, row: f32) -> (f32, f32) {
+        ((column + 0.5) * GRID_SIZE, (row + 0.5) * GRID_SIZE)
+    }
+
+    #[test]
+    fn leads_around_walls() {
+        let field = field("..#..\nN.#..\n.....\n", false);
+        let dir = field.direction(0, cell_center(4.0, 1.0)).unwrap();
+        assert!(dir.sin() > 0.0 && dir.cos() < 0.0);
+        let dir = field.direction(0, cell_center(1.0, 2.0)).unwrap();
+        assert!(dir.sin() < 0.0 && dir.cos() < 0.0);
+    }
+
+    #[test]
+    fn never_cuts_wall_corners() {
+        let field = field("N#.\n.#.\n...\n", false);
+        let dir = field.direction(0, cell_center(2.0, 1.0)).unwrap();
+        assert!(dir.sin() > 0.5, "Heading {} cuts the corner", dir);
+    }
+
+    #[test]
+    fn wraps_across_edges() {
+        let field = field("N..#.\n...#.\n...#.\n", true);
+        let dir = field.direction(0, cell_center(4.0, 1.0)).unwrap();
+        assert!(dir.cos() > 0.0);
+    }
+
+    #[test]
+    fn has_no_direction_when_cut_off_or_home() {
+        let field = field("N#.\n##.\n...\n", false);
+        assert_eq!(field.direction(0, cell_center(2.0, 2.0)), None);
+        assert_eq!(field.direction(0, cell_center(0.0, 0.0)), None);
+        assert_eq!(field.direction(1, cell_center(2.0, 2.0)), None);
+    }
+}
@@ -0,0 +1,174 @@
+use crate::{
+    consts::{COLONY_COLORS, GRID_COLORS, GRID_SIZE},
+    functions::grid_dimensions,
+    simulation::Simulation,
+    webgl::{compile_shader, draw_points, float32_array_view, link_program},
+};
+use std::cell::Cell;
+use wasm_bindgen::JsValue;
+use web_sys::{
+    WebGl2RenderingContext, WebGlBuffer, WebGlProgram, WebGlUniformLocation, WebGlVertexArrayObject,
+};
+
+pub struct GridRenderer {
+    program: WebGlProgram,
+    u_resolution_location: Option<WebGlUniformLocation>,
+    u_grid_size_location: Option<WebGlUniformLocation>,
+    u_columns_location: Option<WebGlUniformLocation>,
+    u_colors_location: Option<WebGlUniformLocation>,
+    u_colony_colors_location: Option<WebGlUniformLocation>,
+    u_food_capacity_location: Option<WebGlUniformLocation>,
+    grid_buffer: WebGlBuffer,
+    vao: WebGlVertexArrayObject,
+    uploaded_version: Cell<u64>,
+}
+
+impl GridRenderer {
+    pub fn new(gl: &WebGl2RenderingContext, simulation: &Simulation) -> Result<Self, JsValue> {
+        let vertex_shader = compile_shader(
+            gl,
+            WebGl2RenderingContext::VERTEX_SHADER,
+            r##"#version 300 es
+
+            in vec2 a_grid;
+
+            uniform vec2 u_resolution;
+            uniform float u_grid_size;
+            uniform int u_columns;
+            uniform mat4 u_colors;
+            uniform vec4 u_colony_colors[4];
+            uniform float u_food_capacity;
+
+            out vec4 v_color;
+            const float eps = 0.001;
+
+            void main() {
+                vec4 color;
+                float intensity = a_grid.y;
+                if (abs(a_grid.x - 1.0) < eps) {
+                    color = u_colors[1] * u_colony_colors[int(a_grid.y + 0.5) % 4];
+                    intensity = 1.0;
+                } else if (abs(a_grid.x - 2.0) < eps) {
+                    color = u_colors[2];
+                    intensity = 0.2 + 0.8 * clamp(a_grid.y / u_food_capacity, 0.0, 1.0);
+                } else if (abs(a_grid.x - 3.0) < eps) {
+                    color = u_colors[3];
+                } else {
+                    color = u_colors[0];
+                }
+                v_color = vec4(color.rgb * intensity, 1.0);
+
+                vec2 coords = vec2(gl_VertexID % u_columns, gl_VertexID / u_columns);
+                vec2 pixel_space = u_grid_size * coords + vec2(u_grid_size / 2.0, u_grid_size / 2.0);
+                vec2 clip_space = 2.0 * pixel_space / u_resolution - 1.0;
+                gl_Position = vec4(clip_space * vec2(1, -1), 0, 1);
+
+                gl_PointSize = u_grid_size;
+            }
+            "##,
+        )
+        .expect("Error creating vertex shader");
+
+        let fragment_shader = compile_shader(
+            gl,
+            WebGl2RenderingContext::FRAGMENT_SHADER,
+            r##"#version 300 es
+
+            precision highp float;
+
+            in vec4 v_color;
+
+            out vec4 out_color;
+
+            void main() {
+                out_color = v_color;
+            }
+            "##,
+        )
+        .expect("Error creating fragment shader");
+
+        let program = link_program(gl, &vertex_shader, &fragment_shader)?;
+
+        let a_grid_location = gl.get_attrib_location(&program, "a_grid");
+
+        let u_resolution_location = gl.get_uniform_location(&program, "u_resolution");
+        let u_grid_size_location = gl.get_uniform_location(&program, "u_grid_size");
+        let u_columns_location = gl.get_uniform_location(&program, "u_columns");
+        let u_colors_location = gl.get_uniform_location(&program, "u_colors");
+        let u_colony_colors_location = gl.get_uniform_location(&program, "u_colony_colors");
+        let u_food_capacity_location = gl.get_uniform_location(&program, "u_food_capacity");
+
+        let grid_buffer = gl.create_buffer().ok_or("Failed to create grid buffer")?;
+        gl.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(&grid_buffer));
+
+        gl.buffer_data_with_array_buffer_view(
+            WebGl2RenderingContext::ARRAY_BUFFER,
+            &float32_array_view(&simulation.grid),
+            WebGl2RenderingContext::DYNAMIC_DRAW,
+        );
+
+        let vao = gl
+            .create_vertex_array()
+            .ok_or("Could not create vertex array object")?;
+        gl.bind_vertex_array(Some(&vao));
+
+        gl.vertex_attrib_pointer_with_i32(
+            a_grid_location as u32,
+            2,
+            WebGl2RenderingContext::FLOAT,
+            false,
+            0,
+            0,
+        );
+        gl.enable_vertex_attrib_array(a_grid_location as u32);
+
+        Ok(GridRenderer {
+            program,
+            vao,
+            u_colors_location,
+            u_colony_colors_location,
+            u_resolution_location,
+            u_grid_size_location,
+            u_columns_location,
+            u_food_capacity_location,
+            grid_buffer,
+            uploaded_version: Cell::new(simulation.grid_version),
+        })
+    }
+
+    pub fn render(&self, gl: &WebGl2RenderingContext, simulation: &Simulation) {
+        gl.use_program(Some(&self.program));
+        gl.bind_vertex_array(Some(&self.vao));
+
+        gl.uniform_matrix4fv_with_f32_array(self.u_colors_location.as_ref(), false, GRID_COLORS);
+        gl.uniform4fv_with_f32_array(self.u_colony_colors_location.as_ref(), COLONY_COLORS);
+        gl.uniform2f(
+            self.u_resolution_location.as_ref(),
+            simulation.width,
+            simulation.height,
+        );
+        gl.uniform1f(self.u_grid_size_location.as_ref(), GRID_SIZE);
+        let (columns, _) = grid_dimensions(simulation.width, simulation.height);
+        gl.uniform1i(self.u_columns_location.as_ref(), columns as i32);
+        gl.uniform1f(
+            self.u_food_capacity_location.as_ref(),
+            simulation.config.food_per_cell,
+        );
+
+        gl.bind_buffer(
+            WebGl2RenderingContext::ARRAY_BUFFER,
+            Some(&self.grid_buffer),
+        );
+
+        if self.uploaded_version.get() != simulation.grid_version {
+            gl.buffer_data_with_array_buffer_view(
+                WebGl2RenderingContext::ARRAY_BUFFER,
+                &float32_array_view(&simulation.grid),
+                WebGl2RenderingContext::DYNAMIC_DRAW,
+            );
+            self.uploaded_version.set(simulation.grid_version);
+        }
+
+        draw_points(gl, (simulation.grid.len() / 2) as i32);
+    }
+}
@@ -1,525 +1,310 @@
 mod functions;
-use consts::{
-    ANT_COLOR, ANT_COUNT, ANT_PHEROMONE_TIMER, ANT_SIZE, ANT_VIEW_ARC, ANT_VIEW_RADIUS,
-    GRID_COLORS, GRID_SIZE, NEST_HONING_STRENGTH, PHEROMONE_COLOR, PHEROMONE_SIZE, PI,
-    WANDER_COEFFICIENT,
-};
 use functions::*;
-use pheromones::PheromoneRenderer;
-use web_sys::{WebGl2RenderingContext, console};
-// use web_sys::console;
-use std::cell::RefCell;
+mod webgl;
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
-use wasm_bindgen::{prelude::*, JsCast};
-mod consts;
-// use easybench_wasm::bench;
+use wasm_bindgen::prelude::*;
+use webgl::*;
+pub mod angle;
+pub mod caste;
+pub mod collision;
+pub mod colony;
+pub mod config;
+pub mod flow_field;
+use config::SimConfig;
 mod ants;
+pub mod consts;
 use ants::*;
+use consts::MAX_BRUSH_RADIUS;
 mod grid;
 use grid::*;
-mod pheromones;
-use rand::prelude::*;
-use rand_xoshiro::rand_core::SeedableRng;
-use rand_xoshiro::Xoshiro256Plus;
+mod grid_renderer;
+use grid_renderer::GridRenderer;
+pub mod map;
+use map::Map;
+pub mod replay;
+use replay::{Intervention, ReplayLog};
+mod pheromone_renderer;
+pub mod pheromones;
+use pheromone_renderer::PheromoneRenderer;
+pub mod simulation;
+pub mod snapshot;
+pub mod spatial;
+pub mod stats;
+use simulation::Simulation;
+use snapshot::Snapshot;
+use stats::ColonyStats;
+
+thread_local! {
+    static SIMULATION: RefCell<Option<Simulation>> = const { RefCell::new(None) };
+    static BRUSH: Cell<(BrushKind, u32)> = const { Cell::new((BrushKind::Wall, 1)) };
+}
+
+fn with_simulation<T>(f: impl FnOnce(&mut Simulation) -> T) -> Result<T, JsValue> {
+    SIMULATION.with(|simulation| {
+        simulation
+            .borrow_mut()
+            .as_mut()
+            .map(f)
+            .ok_or_else(|| JsValue::from("Simulation is not running"))
+    })
+}
 
 #[wasm_bindgen]
-pub fn run() -> Result<(), JsValue> {
+pub fn run(config: Option<SimConfig>) -> Result<(), JsValue> {
     let window = window();
     let (width, height, gl) = get_canvas_dimensions_and_context(&window);
-    let mut rng = Xoshiro256Plus::seed_from_u64(0);
-
-    let (mut ants, mut dirs, mut has_food) = initialize_ants(width, height, ANT_COUNT);
-
-    let nest_coords = (
-        (width / GRID_SIZE) as usize / 2,
-        (height / GRID_SIZE) as usize / 2,
-    );
-
-    let grid = initialize_grid(width, height, nest_coords);
-
-    let mut pheromones: Vec<f32> = vec![-1.0; ANT_COUNT * 3];
-    let mut pheromone_dirs: Vec<f32> = vec![0.0; pheromones.len() / 3];
-
-    // let mut pheromone_map: Vec<u8> = vec![0; (width * height) as usize];
-    // console::log_1(&format!("Normal RNG: {}", bench(|| {
-    //     // bench
-    // })).into());
-
-    // let memory_buffer = wasm_bindgen::memory()
-    //     .dyn_into::<js_sys::WebAssembly::Memory>()
-    //     .unwrap()
-    //     .buffer();
-
-    let grid_vertex_shader = compile_shader(
-        &gl,
-        WebGl2RenderingContext::VERTEX_SHADER,
-        r##"#version 300 es
-
-        in vec2 a_grid;
-
-        uniform vec2 u_resolution;
-        uniform float u_grid_size;
-        uniform mat4 u_colors;
-
-        out vec4 v_color;
-        const float eps = 0.001;
-
-        void main() {
-            vec4 color;
-            if (abs(a_grid.x - 1.0) < eps) {
-                color = u_colors[1];
-            } else if (abs(a_grid.x - 2.0) < eps) {
-                color = u_colors[2];
-            } else if (abs(a_grid.x - 3.0) < eps) {
-                color = u_colors[3];
-            } else {
-                color = u_colors[0];
+    let canvas = get_canvas(&window);
+    attach_paint_handlers(&canvas)?;
+    attach_resize_handler(&window, &canvas)?;
+
+    let simulation = Simulation::new(width, height, config.unwrap_or_default())?;
+
+    let grid_renderer = GridRenderer::new(&gl, &simulation)?;
+    let ant_renderer = AntRenderer::new(&gl)?;
+    let pheromone_renderer = PheromoneRenderer::new(&gl)?;
+
+    SIMULATION.with(|cell| *cell.borrow_mut() = Some(simulation));
+
+    let f = Rc::new(RefCell::new(None));
+    let g = f.clone();
+
+    *g.borrow_mut() = Some(Closure::new(move || {
+        with_simulation(|simulation| {
+            let (width, height) = (canvas.width() as f32, canvas.height() as f32);
+            if (width, height) != (simulation.width, simulation.height) {
+                // Replays keep their recorded size, and worlds can't shrink
+                // below one grid cell.
+                let resized = !simulation.is_replaying()
+                    && simulation
+                        .apply(Intervention::Resize { width, height })
+                        .is_ok();
+                if !resized {
+                    canvas.set_width(simulation.width as u32);
+                    canvas.set_height(simulation.height as u32);
+                }
             }
-            v_color = vec4(color.rgb * a_grid.y, 1.0);
+            gl.viewport(0, 0, simulation.width as i32, simulation.height as i32);
 
-            vec2 coords = vec2(gl_VertexID % 120, gl_VertexID / 120);
-            vec2 pixel_space = u_grid_size * coords + vec2(u_grid_size / 2.0, u_grid_size / 2.0);
-            vec2 clip_space = 2.0 * pixel_space / u_resolution - 1.0;
-            gl_Position = vec4(clip_space * vec2(1, -1), 0, 1);
+            simulation.step();
 
-            gl_PointSize = u_grid_size;
-        }
-        "##,
-    )
-    .expect("Error creating vertex shader");
+            clear(&gl);
+            grid_renderer.render(&gl, simulation);
+            pheromone_renderer.render(&gl, simulation);
+            ant_renderer.render(&gl, simulation);
+        })
+        .expect("Simulation was dropped while running");
 
-    let grid_fragment_shader = compile_shader(
-        &gl,
-        WebGl2RenderingContext::FRAGMENT_SHADER,
-        r##"#version 300 es
-        
-        precision highp float;
+        request_animation_frame(f.borrow().as_ref().unwrap());
+    }));
 
-        in vec4 v_color;
+    request_animation_frame(g.borrow().as_ref().unwrap());
 
-        out vec4 out_color;
+    Ok(())
+}
 
-        void main() {
-            out_color = v_color;
-        }
-        "##,
-    )
-    .expect("Error creating fragment shader");
-
-    let grid_program = link_program(&gl, &grid_vertex_shader, &grid_fragment_shader)?;
-
-    let a_grid_location = gl.get_attrib_location(&grid_program, "a_grid");
-
-    let u_grid_resolution_location = gl.get_uniform_location(&grid_program, "u_resolution");
-    let u_grid_size_location = gl.get_uniform_location(&grid_program, "u_grid_size");
-    let u_grid_colors_location = gl.get_uniform_location(&grid_program, "u_colors");
-
-    let grid_buffer = gl.create_buffer().ok_or("Failed to create grid buffer")?;
-    gl.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(&grid_buffer));
-
-    let grid_location: u32 = grid.as_ptr() as u32 / 4;
-    let grid_next_location = grid_location + grid.len() as u32;
-
-    let memory_buffer = wasm_bindgen::memory()
-        .dyn_into::<js_sys::WebAssembly::Memory>()
-        .unwrap()
-        .buffer();
-
-    let grid_array_buf_view =
-        js_sys::Float32Array::new(&memory_buffer).subarray(grid_location, grid_next_location);
-
-    gl.buffer_data_with_array_buffer_view(
-        WebGl2RenderingContext::ARRAY_BUFFER,
-        &grid_array_buf_view,
-        WebGl2RenderingContext::DYNAMIC_DRAW,
-    );
-
-    let grid_vao = gl
-        .create_vertex_array()
-        .ok_or("Could not create vertex array object")?;
-    gl.bind_vertex_array(Some(&grid_vao));
-
-    gl.vertex_attrib_pointer_with_i32(
-        a_grid_location as u32,
-        2,
-        WebGl2RenderingContext::FLOAT,
-        false,
-        0,
-        0,
-    );
-    gl.enable_vertex_attrib_array(a_grid_location as u32);
-
-    let grid_vertex_count = (grid.len() / 2) as i32;
-
-    let ants_vertex_shader = compile_shader(
-        &gl,
-        WebGl2RenderingContext::VERTEX_SHADER,
-        r##"#version 300 es
-
-        in vec2 a_position;
-        uniform vec2 u_resolution;
-        uniform float u_ant_size;
-
-        void main() {
-            vec2 clip_space = 2.0 * a_position / u_resolution - 1.0;
-            gl_Position = vec4(clip_space * vec2(1, -1), 0, 1);
-            gl_PointSize = u_ant_size;
-        }
-        "##,
-    )
-    .expect("Error creating vertex shader");
-
-    let ants_fragment_shader = compile_shader(
-        &gl,
-        WebGl2RenderingContext::FRAGMENT_SHADER,
-        r##"#version 300 es
-        
-        precision highp float;
-        uniform vec4 u_color;
-        out vec4 out_color;
-
-        void main() {
-            out_color = u_color;
-        }
-        "##,
-    )
-    .expect("Error creating fragment shader");
-
-    let ants_program = link_program(&gl, &ants_vertex_shader, &ants_fragment_shader)?;
-
-    let a_ants_position_location = gl.get_attrib_location(&ants_program, "a_position");
-
-    let u_ants_resolution_location = gl.get_uniform_location(&ants_program, "u_resolution");
-    let u_ant_size_location = gl.get_uniform_location(&ants_program, "u_ant_size");
-    let u_ant_color_location = gl.get_uniform_location(&ants_program, "u_color");
-
-    let ants_position_buffer = gl.create_buffer().ok_or("Failed to create buffer")?;
-    gl.bind_buffer(
-        WebGl2RenderingContext::ARRAY_BUFFER,
-        Some(&ants_position_buffer),
-    );
-
-    let ants_location: u32 = ants.as_ptr() as u32 / 4;
-    let ants_next_location = ants_location + ants.len() as u32;
-
-    // let memory_buffer = wasm_bindgen::memory()
-    //     .dyn_into::<js_sys::WebAssembly::Memory>()
-    //     .unwrap()
-    //     .buffer();
-
-    let ant_positions_array_buf_view =
-        js_sys::Float32Array::new(&memory_buffer).subarray(ants_location, ants_next_location);
-
-    gl.buffer_data_with_array_buffer_view(
-        WebGl2RenderingContext::ARRAY_BUFFER,
-        &ant_positions_array_buf_view,
-        WebGl2RenderingContext::DYNAMIC_DRAW,
-    );
-
-    let ants_vao = gl
-        .create_vertex_array()
-        .ok_or("Could not create vertex array object")?;
-    gl.bind_vertex_array(Some(&ants_vao));
-
-    gl.vertex_attrib_pointer_with_i32(
-        a_ants_position_location as u32,
-        2,
-        WebGl2RenderingContext::FLOAT,
-        false,
-        0,
-        0,
-    );
-    gl.enable_vertex_attrib_array(a_ants_position_location as u32);
-
-    let ants_vertex_count = (ants.len() / 2) as i32;
-
-    let mut pheromone_timer = ANT_PHEROMONE_TIMER;
-
-    let pheromone_vertex_shader = compile_shader(
-        &gl,
-        WebGl2RenderingContext::VERTEX_SHADER,
-        r##"#version 300 es
-
-            in vec3 a_position;
-            uniform vec2 u_resolution;
-            uniform float u_pheromone_size;
-            uniform vec4 u_color;
-            out vec4 v_color;
-
-            void main() {
-                vec2 clip_space = 2.0 * a_position.xy / u_resolution - 1.0;
-                gl_Position = vec4(clip_space * vec2(1, -1), 0, 1);
-                gl_PointSize = u_pheromone_size;
-                v_color = vec4(u_color.rgb * a_position.z, 1.0);
+/// Keeps the canvas backing size in sync with its layout size. The frame loop
+/// picks up the new dimensions and resizes the simulation.
+fn attach_resize_handler(
+    window: &web_sys::Window,
+    canvas: &web_sys::HtmlCanvasElement,
+) -> Result<(), JsValue> {
+    let on_resize = {
+        let canvas = canvas.clone();
+        Closure::<dyn FnMut()>::new(move || {
+            let (client_width, client_height) = (canvas.client_width(), canvas.client_height());
+            if client_width > 0 && client_height > 0 {
+                canvas.set_width(client_width as u32);
+                canvas.set_height(client_height as u32);
             }
-            "##,
-    )
-    .expect("Error creating vertex shader");
-
-    let pheromone_fragment_shader = compile_shader(
-        &gl,
-        WebGl2RenderingContext::FRAGMENT_SHADER,
-        r##"#version 300 es
-            
-            precision highp float;
-            
-            in vec4 v_color;
-            out vec4 out_color;
-
-            void main() {
-                out_color = v_color;
+        })
+    };
+    window.add_event_listener_with_callback("resize", on_resize.as_ref().unchecked_ref())?;
+    on_resize.forget();
+    Ok(())
+}
+
+fn attach_paint_handlers(canvas: &web_sys::HtmlCanvasElement) -> Result<(), JsValue> {
+    let painting = Rc::new(Cell::new(false));
+
+    let paint = {
+        let canvas = canvas.clone();
+        move |event: &web_sys::PointerEvent| {
+            let (brush, radius) = BRUSH.with(|brush| brush.get());
+            let pos = pointer_position(&canvas, event);
+            // Ignore events that arrive before `run` has stored the simulation.
+            let _ = with_simulation(|simulation| {
+                simulation.apply(Intervention::Paint { pos, brush, radius })
+            });
+        }
+    };
+
+    let on_down = {
+        let canvas = canvas.clone();
+        let painting = painting.clone();
+        let paint = paint.clone();
+        Closure::<dyn FnMut(_)>::new(move |event: web_sys::PointerEvent| {
+            painting.set(true);
+            let _ = canvas.set_pointer_capture(event.pointer_id());
+            paint(&event);
+        })
+    };
+    let on_move = {
+        let painting = painting.clone();
+        Closure::<dyn FnMut(_)>::new(move |event: web_sys::PointerEvent| {
+            if painting.get() {
+                paint(&event);
             }
-            "##,
-    )
-    .expect("Error creating fragment shader");
-
-    let pheromone_program =
-        link_program(&gl, &pheromone_vertex_shader, &pheromone_fragment_shader)?;
-
-    let a_pheromone_position_location = gl.get_attrib_location(&pheromone_program, "a_position");
-
-    let u_pheromone_resolution_location =
-        gl.get_uniform_location(&pheromone_program, "u_resolution");
-    let u_pheromone_size_location = gl.get_uniform_location(&pheromone_program, "u_pheromone_size");
-    let u_pheromone_color_location = gl.get_uniform_location(&pheromone_program, "u_color");
-
-    let pheromone_position_buffer = gl.create_buffer().ok_or("Failed to create buffer")?;
-    gl.bind_buffer(
-        WebGl2RenderingContext::ARRAY_BUFFER,
-        Some(&pheromone_position_buffer),
-    );
-
-    let pheromone_location: u32 = pheromones.as_ptr() as u32 / 4;
-    let pheromone_next_location = pheromone_location + pheromones.len() as u32;
-
-    // let memory_buffer = wasm_bindgen::memory()
-    //     .dyn_into::<js_sys::WebAssembly::Memory>()
-    //     .unwrap()
-    //     .buffer();
-
-    let pheromone_positions_array_buf_view = js_sys::Float32Array::new(&memory_buffer)
-        .subarray(pheromone_location, pheromone_next_location);
-
-    gl.buffer_data_with_array_buffer_view(
-        WebGl2RenderingContext::ARRAY_BUFFER,
-        &pheromone_positions_array_buf_view,
-        WebGl2RenderingContext::DYNAMIC_DRAW,
-    );
-
-    let pheromone_vao = gl
-        .create_vertex_array()
-        .ok_or("Could not create vertex array object")?;
-    gl.bind_vertex_array(Some(&pheromone_vao));
-
-    gl.vertex_attrib_pointer_with_i32(
-        a_pheromone_position_location as u32,
-        3,
-        WebGl2RenderingContext::FLOAT,
-        false,
-        0,
-        0,
-    );
-    gl.enable_vertex_attrib_array(a_pheromone_position_location as u32);
-
-    let pheromone_vertex_count = (pheromones.len() / 3) as i32;
-
-    // let grid_renderer = GridRenderer::new(&gl, width, height)
-    //     .expect("Error initializing grid renderer");
-    // let mut ant_renderer =
-    //     AntRenderer::new(&gl, width, height).expect("Error initializing ant renderer");
-    // let mut pheromone_renderer =
-    //     PheromoneRenderer::new(&gl, width, height).expect("Error initializing pheromone renderer");
+        })
+    };
+    let on_up = Closure::<dyn FnMut(_)>::new(move |_event: web_sys::PointerEvent| {
+        painting.set(false);
+    });
 
-    let f = Rc::new(RefCell::new(None));
-    let g = f.clone();
+    canvas.add_event_listener_with_callback("pointerdown", on_down.as_ref().unchecked_ref())?;
+    canvas.add_event_listener_with_callback("pointermove", on_move.as_ref().unchecked_ref())?;
+    canvas.add_event_listener_with_callback("pointerup", on_up.as_ref().unchecked_ref())?;
+    canvas.add_event_listener_with_callback("pointercancel", on_up.as_ref().unchecked_ref())?;
 
-    *g.borrow_mut() = Some(Closure::new(move || {
-        clear(&gl);
-        gl.use_program(Some(&grid_program));
-        gl.bind_vertex_array(Some(&grid_vao));
+    on_down.forget();
+    on_move.forget();
+    on_up.forget();
 
-        gl.uniform_matrix4fv_with_f32_array(u_grid_colors_location.as_ref(), false, GRID_COLORS);
-        gl.uniform2f(u_grid_resolution_location.as_ref(), width, height);
-        gl.uniform1f(u_grid_size_location.as_ref(), GRID_SIZE);
+    Ok(())
+}
 
-        gl.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(&grid_buffer));
+#[wasm_bindgen]
+pub fn set_brush(kind: BrushKind, radius: u32) {
+    BRUSH.with(|brush| brush.set((kind, radius.min(MAX_BRUSH_RADIUS))));
+}
 
-        // gl.buffer_data_with_array_buffer_view(
-        //     WebGl2RenderingContext::ARRAY_BUFFER,
-        //     &self.grid_array_buf_view,
-        //     WebGl2RenderingContext::STATIC_DRAW,
-        // );
+#[wasm_bindgen]
+pub fn resize_canvas(width: u32, height: u32) -> Result<(), JsValue> {
+    check_world_size(width as f32, height as f32)?;
+    let canvas = get_canvas(&window());
+    canvas.set_width(width);
+    canvas.set_height(height);
+    Ok(())
+}
 
-        draw_points(&gl, grid_vertex_count);
+#[wasm_bindgen]
+pub fn get_config() -> Result<SimConfig, JsValue> {
+    with_simulation(|simulation| simulation.config)
+}
+
+#[wasm_bindgen]
+pub fn set_config(config: SimConfig) -> Result<(), JsValue> {
+    with_simulation(|simulation| simulation.apply(Intervention::SetConfig { config }))??;
+    Ok(())
+}
+
+#[wasm_bindgen]
+pub fn colony_stats() -> Result<ColonyStats, JsValue> {
+    with_simulation(|simulation| simulation.stats())
+}
+
+#[wasm_bindgen]
+pub fn colony_count() -> Result<usize, JsValue> {
+    with_simulation(|simulation| simulation.colonies.len())
+}
 
-        gl.use_program(Some(&ants_program));
-        gl.bind_vertex_array(Some(&ants_vao));
+#[wasm_bindgen]
+pub fn colony_stats_for(colony: usize) -> Result<ColonyStats, JsValue> {
+    with_simulation(|simulation| simulation.colony_stats(colony))?
+        .ok_or_else(|| JsValue::from(format!("No colony {}", colony)))
+}
 
-        gl.uniform4fv_with_f32_array(u_ant_color_location.as_ref(), ANT_COLOR);
-        gl.uniform2f(u_ants_resolution_location.as_ref(), width, height);
-        gl.uniform1f(u_ant_size_location.as_ref(), ANT_SIZE);
+/// Food stored by each colony, indexed by colony.
+#[wasm_bindgen]
+pub fn colony_food_totals() -> Result<Vec<f32>, JsValue> {
+    with_simulation(|simulation| {
+        simulation
+            .colonies
+            .iter()
+            .map(|colony| colony.stats.food_stored)
+            .collect()
+    })
+}
 
-        gl.bind_buffer(
-            WebGl2RenderingContext::ARRAY_BUFFER,
-            Some(&ants_position_buffer),
-        );
+#[wasm_bindgen]
+pub fn load_map(map: &str) -> Result<(), JsValue> {
+    let map = Intervention::LoadMap {
+        map: map.to_string(),
+    };
+    with_simulation(|simulation| simulation.apply(map))??;
+    Ok(())
+}
 
-        gl.buffer_data_with_array_buffer_view(
-            WebGl2RenderingContext::ARRAY_BUFFER,
-            &ant_positions_array_buf_view,
-            WebGl2RenderingContext::DYNAMIC_DRAW,
-        );
+#[wasm_bindgen]
+pub fn load_map_png(bytes: &[u8]) -> Result<(), JsValue> {
+    let map = Map::parse_png(bytes).map_err(|e| JsValue::from(e.to_string()))?;
+    let map = Intervention::LoadMap {
+        map: map.to_ascii().map_err(|e| JsValue::from(e.to_string()))?,
+    };
+    with_simulation(|simulation| simulation.apply(map))??;
+    Ok(())
+}
 
-        draw_points(&gl, ants_vertex_count);
+#[wasm_bindgen]
+pub fn save_map() -> Result<String, JsValue> {
+    with_simulation(|simulation| simulation.to_map().to_ascii())?
+        .map_err(|e| JsValue::from(e.to_string()))
+}
 
-        pheromone_timer -= 1;
+#[wasm_bindgen]
+pub fn save_state() -> Result<Vec<u8>, JsValue> {
+    with_simulation(|simulation| simulation.snapshot().to_bytes())
+}
 
-        for idx in (0..ants.len()).step_by(2) {
-            let (part1, part2) = ants.split_at_mut(idx + 1);
-            let x = part1.last_mut().expect("Error indexing vector");
-            let y = part2.first_mut().expect("Error indexing vector");
-            let dir = &mut dirs[idx / 2];
-            if *dir >= PI {
-                *dir -= 2.0 * PI;
-            }
-            if *dir < -1.0 * PI {
-                *dir += 2.0 * PI;
-            }
-            let mut next_dir = dir.clone();
-            if has_food[idx / 2] == true {
-                let dir_diff = dir_to_nest((*x, *y), nest_coords) - next_dir;
-                next_dir = next_dir + dir_diff * NEST_HONING_STRENGTH;
-                if pheromone_timer == 0 {
-                    add_pheromone(&mut pheromones, &mut pheromone_dirs, (*x, *y), next_dir + PI);
-                }
-            } else {
-                // let mut weighted_dirs: Vec<(f32, f32)> = Vec::new();
-                // let mut weight_sum: f32 = 0.0;
-                let mut max_strength = 0.0;
-                for idx in (0..pheromones.len()).step_by(3) {
-                    let pher_x = pheromones[idx];
-                    let pher_y = pheromones[idx + 1];
-                    let pher_dist = calc_dist((*x, *y), (pher_x, pher_y));
-                    // let pher_loc = calc_dir((*x, *y), (pher_x, pher_y));
-                    let pher_dir = pheromone_dirs[idx / 3];
-                    if pher_dist <= ANT_VIEW_RADIUS {
-                        let pher_s = pheromones[idx + 2];
-                        if pher_s > max_strength {
-                            max_strength = pher_s;
-                            next_dir = pher_dir;
-                            console::log_1(&JsValue::from(next_dir));
-                        }
-                        // weighted_dirs.push((pheromone_dirs[idx / 3], pher_s));
-                        // weight_sum += pher_s;
-                    }
-                }
-                // match weighted_dirs.len() {
-                //     0 => (),
-                //     _ => {
-                //         let pheromone_dir = weighted_dirs
-                //             .iter()
-                //             .fold(0.0, |accum, val| accum + val.0 * val.1)
-                //             / weight_sum;
-                //         next_dir = pheromone_dir;
-                //     }
-                // }
-            }
-            next_dir += (rng.gen::<f32>() - 0.5) * WANDER_COEFFICIENT;
-            let mut next_pos = (*x, *y);
-            for i in 0..4 {
-                next_dir = match i {
-                    0 => next_dir,
-                    1 => *dir - 2.0 * *dir,
-                    2 => *dir + (PI / 2.0 - *dir) * 2.0,
-                    _ => *dir + PI,
-                };
-                next_pos = next_ant_position((*x, *y), next_dir);
-                match get_resource_at_position(&grid, width, height, next_pos) {
-                    GridResource::Blank => {
-                        break;
-                    }
-                    GridResource::Food => {
-                        if has_food[idx / 2] {
-                            break;
-                        }
-                        has_food[idx / 2] = true;
-                        next_dir = *dir + PI;
-                        next_pos = next_ant_position((*x, *y), next_dir);
-                        if get_resource_at_position(&grid, width, height, next_pos)
-                            != GridResource::Blank
-                        {
-                            next_pos = next_ant_position((*x, *y), *dir);
-                            next_dir = *dir;
-                        }
-                        break;
-                    }
-                    GridResource::Nest => {
-                        has_food[idx / 2] = false;
-                        next_dir = *dir + PI;
-                        next_pos = next_ant_position((*x, *y), next_dir);
-                        if get_resource_at_position(&grid, width, height, next_pos)
-                            != GridResource::Blank
-                        {
-                            next_pos = next_ant_position((*x, *y), *dir);
-                            next_dir = *dir;
-                        }
-                        break;
-                    }
-                    GridResource::Wall => {
-                        continue;
-                    }
-                }
-            }
-            (*x, *y) = next_pos;
-            *dir = next_dir;
-        }
+#[wasm_bindgen]
+pub fn load_state(bytes: &[u8]) -> Result<(), JsValue> {
+    restore_snapshot(Snapshot::from_bytes(bytes)?)
+}
 
-        if pheromone_timer <= 0 {
-            pheromone_timer = ANT_PHEROMONE_TIMER;
-        }
+#[wasm_bindgen]
+pub fn save_state_json() -> Result<String, JsValue> {
+    with_simulation(|simulation| simulation.snapshot().to_json())
+}
 
-        gl.use_program(Some(&pheromone_program));
-        gl.bind_vertex_array(Some(&pheromone_vao));
-
-        gl.uniform4fv_with_f32_array(u_pheromone_color_location.as_ref(), PHEROMONE_COLOR);
-        gl.uniform2f(u_pheromone_resolution_location.as_ref(), width, height);
-        gl.uniform1f(u_pheromone_size_location.as_ref(), PHEROMONE_SIZE);
-
-        gl.bind_buffer(
-            WebGl2RenderingContext::ARRAY_BUFFER,
-            Some(&pheromone_position_buffer),
-        );
-
-        gl.buffer_data_with_array_buffer_view(
-            WebGl2RenderingContext::ARRAY_BUFFER,
-            &pheromone_positions_array_buf_view,
-            WebGl2RenderingContext::STATIC_DRAW,
-        );
-
-        draw_points(&gl, pheromone_vertex_count);
-
-        for idx in (0..pheromones.len()).step_by(3) {
-            let (part1, part2) = pheromones.split_at_mut(idx + 1);
-            let x = part1.last_mut().expect("Error indexing vector");
-            let (part3, part4) = part2.split_at_mut(1);
-            let y = part3.last_mut().expect("Error indexing vector");
-            let str = part4.first_mut().expect("Error indexing vector");
-            *str -= 0.003;
-            if *str <= 0.0 {
-                *x = -1.0;
-                *y = -1.0;
-                *str = -1.0;
-            }
-        }
+#[wasm_bindgen]
+pub fn load_state_json(json: &str) -> Result<(), JsValue> {
+    restore_snapshot(Snapshot::from_json(json)?)
+}
 
-        // grid_renderer.render(&gl);
-        // ant_renderer.render(&gl, &mut rng, &grid_renderer, &mut pheromone_renderer);
-        // pheromone_renderer.render(&gl);
+/// Restores `snapshot` and resizes the canvas to match so the frame loop
+/// doesn't crop the restored world.
+fn restore_snapshot(snapshot: Snapshot) -> Result<(), JsValue> {
+    let (width, height) = (snapshot.width as u32, snapshot.height as u32);
+    with_simulation(|simulation| simulation.restore(snapshot))??;
+    resize_canvas(width, height)?;
+    Ok(())
+}
 
-        request_animation_frame(f.borrow().as_ref().unwrap());
-    }));
+/// Restarts the simulation and starts logging interventions for replay.
+#[wasm_bindgen]
+pub fn start_recording() -> Result<(), JsValue> {
+    with_simulation(|simulation| simulation.start_recording())??;
+    Ok(())
+}
 
-    request_animation_frame(g.borrow().as_ref().unwrap());
+/// Stops recording and returns the replay log as JSON.
+#[wasm_bindgen]
+pub fn stop_recording() -> Result<String, JsValue> {
+    with_simulation(|simulation| simulation.stop_recording())?
+        .map(|log| log.to_json())
+        .ok_or_else(|| JsValue::from("Not recording"))
+}
 
+#[wasm_bindgen]
+pub fn replay(log: &str) -> Result<(), JsValue> {
+    let log = ReplayLog::from_json(log)?;
+    let (width, height) = (log.width as u32, log.height as u32);
+    with_simulation(|simulation| simulation.start_replay(log))??;
+    resize_canvas(width, height)?;
     Ok(())
 }
+
+#[wasm_bindgen]
+pub fn is_replaying() -> Result<bool, JsValue> {
+    with_simulation(|simulation| simulation.is_replaying())
+}
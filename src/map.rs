@@ -0,0 +1,304 @@
+//! Map files describe the initial contents of the grid, one character or
+//! pixel per `GRID_SIZE` cell, starting from the top-left corner.
+//!
+//! ASCII maps use one line per grid row:
+//!
+//! ```text
+//! ; lines starting with ';' are comments
+//! ..........#.........
+//! ..NN......#....FFF..
+//! ..NN......#.........
+//! ```
+//!
+//! | Character   | Cell                |
+//! |-------------|---------------------|
+//! | `.` / space | Blank               |
+//! | `N` / `0`   | Nest of colony 0    |
+//! | `1` - `9`   | Nest of that colony |
+//! | `F`         | Food                |
+//! | `#`         | Wall                |
+//!
+//! PNG maps use one pixel per cell and are matched to the nearest palette
+//! colour: black is blank, magenta (`#FF00FF`) is nest, green (`#00FF00`) is
+//! food and grey (`#808080`) is wall. Pixels with alpha below 128 are blank.
+//! PNG nests always belong to colony 0.
+//!
+//! Maps may be smaller than the grid, in which case the remaining cells are
+//! blank. Maps larger than the grid, maps without a nest and maps that skip a
+//! colony number are rejected.
+
+use crate::grid::GridResource;
+use std::fmt;
+
+const PNG_PALETTE: [([u8; 3], GridResource); 4] = [
+    ([0, 0, 0], GridResource::Blank),
+    ([255, 0, 255], GridResource::Nest(0)),
+    ([0, 255, 0], GridResource::Food),
+    ([128, 128, 128], GridResource::Wall),
+];
+
+#[derive(Debug, PartialEq)]
+pub enum MapError {
+    UnknownCell {
+        line: usize,
+        column: usize,
+        found: char,
+    },
+    OutOfBounds {
+        columns: usize,
+        rows: usize,
+        max_columns: usize,
+        max_rows: usize,
+    },
+    MissingNest,
+    MissingColony(usize),
+    /// ASCII maps only have the digits 0-9 for colonies.
+    ColonyNotWritable(usize),
+    InvalidPng(String),
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapError::UnknownCell {
+                line,
+                column,
+                found,
+            } => write!(
+                f,
+                "Unknown map cell '{}' at line {}, column {}",
+                found, line, column
+            ),
+            MapError::OutOfBounds {
+                columns,
+                rows,
+                max_columns,
+                max_rows,
+            } => write!(
+                f,
+                "Map is {}x{} cells but the grid is only {}x{}",
+                columns, rows, max_columns, max_rows
+            ),
+            MapError::MissingNest => write!(f, "Map has no nest cells"),
+            MapError::MissingColony(colony) => {
+                write!(f, "Map has no nest cells for colony {}", colony)
+            }
+            MapError::ColonyNotWritable(colony) => write!(
+                f,
+                "Colony {} can't be written to an ASCII map, which holds colonies 0-9",
+                colony
+            ),
+            MapError::InvalidPng(message) => write!(f, "Invalid PNG map: {}", message),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Map {
+    pub columns: usize,
+    pub rows: usize,
+    pub cells: Vec<GridResource>,
+}
+
+impl Map {
+    pub fn parse_ascii(source: &str) -> Result<Map, MapError> {
+        let mut lines = Vec::new();
+        for (line_idx, line) in source.lines().enumerate() {
+            if line.starts_with(';') {
+                continue;
+            }
+            let mut row = Vec::new();
+            for (column, ch) in line.trim_end().chars().enumerate() {
+                row.push(match ch {
+                    '.' | ' ' => GridResource::Blank,
+                    'N' => GridResource::Nest(0),
+                    '0'..='9' => GridResource::Nest(ch as usize - '0' as usize),
+                    'F' => GridResource::Food,
+                    '#' => GridResource::Wall,
+                    found => {
+                        return Err(MapError::UnknownCell {
+                            line: line_idx + 1,
+                            column: column + 1,
+                            found,
+                        })
+                    }
+                });
+            }
+            lines.push(row);
+        }
+        while lines.last().is_some_and(|row| row.is_empty()) {
+            lines.pop();
+        }
+
+        let columns = lines.iter().map(|row| row.len()).max().unwrap_or(0);
+        let rows = lines.len();
+        let mut cells = Vec::with_capacity(columns * rows);
+        for mut row in lines {
+            row.resize(columns, GridResource::Blank);
+            cells.extend(row);
+        }
+        Map::validated(columns, rows, cells)
+    }
+
+    pub fn parse_png(bytes: &[u8]) -> Result<Map, MapError> {
+        let mut decoder = png::Decoder::new(bytes);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder
+            .read_info()
+            .map_err(|e| MapError::InvalidPng(e.to_string()))?;
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader
+            .next_frame(&mut pixels)
+            .map_err(|e| MapError::InvalidPng(e.to_string()))?;
+
+        let channels = info.color_type.samples();
+        let columns = info.width as usize;
+        let rows = info.height as usize;
+        let mut cells = Vec::with_capacity(columns * rows);
+        for row in 0..rows {
+            for column in 0..columns {
+                let offset = row * info.line_size + column * channels;
+                let pixel = &pixels[offset..offset + channels];
+                let (rgb, alpha) = match channels {
+                    1 => ([pixel[0]; 3], 255),
+                    2 => ([pixel[0]; 3], pixel[1]),
+                    3 => ([pixel[0], pixel[1], pixel[2]], 255),
+                    _ => ([pixel[0], pixel[1], pixel[2]], pixel[3]),
+                };
+                cells.push(if alpha < 128 {
+                    GridResource::Blank
+                } else {
+                    nearest_palette_resource(rgb)
+                });
+            }
+        }
+        Map::validated(columns, rows, cells)
+    }
+
+    fn validated(columns: usize, rows: usize, cells: Vec<GridResource>) -> Result<Map, MapError> {
+        let mut colonies = Vec::new();
+        for cell in &cells {
+            if let GridResource::Nest(colony) = *cell {
+                if colony >= colonies.len() {
+                    colonies.resize(colony + 1, false);
+                }
+                colonies[colony] = true;
+            }
+        }
+        if colonies.is_empty() {
+            return Err(MapError::MissingNest);
+        }
+        if let Some(colony) = colonies.iter().position(|&present| !present) {
+            return Err(MapError::MissingColony(colony));
+        }
+        Ok(Map {
+            columns,
+            rows,
+            cells,
+        })
+    }
+
+    pub fn check_bounds(&self, max_columns: usize, max_rows: usize) -> Result<(), MapError> {
+        if self.columns > max_columns || self.rows > max_rows {
+            return Err(MapError::OutOfBounds {
+                columns: self.columns,
+                rows: self.rows,
+                max_columns,
+                max_rows,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn to_ascii(&self) -> Result<String, MapError> {
+        let mut ascii = String::with_capacity((self.columns + 1) * self.rows);
+        for row in self.cells.chunks(self.columns.max(1)) {
+            for cell in row {
+                ascii.push(match *cell {
+                    GridResource::Blank => '.',
+                    GridResource::Nest(0) => 'N',
+                    GridResource::Nest(colony) => char::from_digit(colony as u32, 10)
+                        .ok_or(MapError::ColonyNotWritable(colony))?,
+                    GridResource::Food => 'F',
+                    GridResource::Wall => '#',
+                });
+            }
+            ascii.push('\n');
+        }
+        Ok(ascii)
+    }
+}
+
+fn nearest_palette_resource(rgb: [u8; 3]) -> GridResource {
+    let distance = |color: &[u8; 3]| -> i32 {
+        (0..3)
+            .map(|i| (color[i] as i32 - rgb[i] as i32).pow(2))
+            .sum()
+    };
+    PNG_PALETTE
+        .iter()
+        .min_by_key(|(color, _)| distance(color))
+        .map(|(_, resource)| *resource)
+        .unwrap_or(GridResource::Blank)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ascii_round_trips() {
+        let source = "..N..F\n.#..1.\n..2...\n";
+        let map = Map::parse_ascii(source).unwrap();
+        assert_eq!((map.columns, map.rows), (6, 3));
+        assert_eq!(map.to_ascii().unwrap(), source);
+        assert_eq!(Map::parse_ascii(&map.to_ascii().unwrap()).unwrap(), map);
+    }
+
+    #[test]
+    fn rejects_unknown_cells() {
+        assert_eq!(
+            Map::parse_ascii("; comment\nN..\n.x.\n"),
+            Err(MapError::UnknownCell {
+                line: 3,
+                column: 2,
+                found: 'x'
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_maps_larger_than_the_grid() {
+        let map = Map::parse_ascii("N...\n....\n").unwrap();
+        assert_eq!(map.check_bounds(4, 2), Ok(()));
+        assert_eq!(
+            map.check_bounds(3, 2),
+            Err(MapError::OutOfBounds {
+                columns: 4,
+                rows: 2,
+                max_columns: 3,
+                max_rows: 2
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_maps_without_nests() {
+        assert_eq!(Map::parse_ascii("..F\n.#.\n"), Err(MapError::MissingNest));
+    }
+
+    #[test]
+    fn rejects_skipped_colonies() {
+        assert_eq!(Map::parse_ascii("N.2\n"), Err(MapError::MissingColony(1)));
+    }
+
+    #[test]
+    fn rejects_colonies_past_nine_when_writing_ascii() {
+        let map = Map {
+            columns: 2,
+            rows: 1,
+            cells: vec![GridResource::Nest(0), GridResource::Nest(10)],
+        };
+        assert_eq!(map.to_ascii(), Err(MapError::ColonyNotWritable(10)));
+    }
+}
@@ -0,0 +1,158 @@
+use crate::colony::tint;
+use crate::pheromones::PheromoneChannel;
+use crate::simulation::Simulation;
+use crate::webgl::{compile_shader, draw_points, float32_array_view, link_program};
+use wasm_bindgen::JsValue;
+use web_sys::WebGlBuffer;
+use web_sys::WebGlVertexArrayObject;
+use web_sys::{WebGl2RenderingContext, WebGlProgram, WebGlUniformLocation};
+
+pub struct PheromoneRenderer {
+    program: WebGlProgram,
+    u_resolution_location: Option<WebGlUniformLocation>,
+    u_cell_size_location: Option<WebGlUniformLocation>,
+    u_columns_location: Option<WebGlUniformLocation>,
+    u_color_location: Option<WebGlUniformLocation>,
+    strength_buffer: WebGlBuffer,
+    vao: WebGlVertexArrayObject,
+}
+
+impl PheromoneRenderer {
+    pub fn new(gl: &WebGl2RenderingContext) -> Result<Self, JsValue> {
+        let vertex_shader = compile_shader(
+            gl,
+            WebGl2RenderingContext::VERTEX_SHADER,
+            r##"#version 300 es
+
+            in float a_strength;
+            uniform vec2 u_resolution;
+            uniform float u_cell_size;
+            uniform int u_columns;
+            uniform vec4 u_color;
+            out vec4 v_color;
+
+            void main() {
+                vec2 coords = vec2(gl_VertexID % u_columns, gl_VertexID / u_columns);
+                vec2 pixel_space = u_cell_size * coords + vec2(u_cell_size / 2.0, u_cell_size / 2.0);
+                vec2 clip_space = 2.0 * pixel_space / u_resolution - 1.0;
+                gl_Position = vec4(clip_space * vec2(1, -1), 0, 1);
+                gl_PointSize = u_cell_size;
+                v_color = vec4(u_color.rgb, clamp(a_strength, 0.0, 1.0));
+            }
+            "##,
+        )
+        .expect("Error creating vertex shader");
+
+        let fragment_shader = compile_shader(
+            gl,
+            WebGl2RenderingContext::FRAGMENT_SHADER,
+            r##"#version 300 es
+
+            precision highp float;
+
+            in vec4 v_color;
+            out vec4 out_color;
+
+            void main() {
+                if (v_color.a <= 0.0) {
+                    discard;
+                }
+                out_color = v_color;
+            }
+            "##,
+        )
+        .expect("Error creating fragment shader");
+
+        let program = link_program(gl, &vertex_shader, &fragment_shader)?;
+
+        let a_strength_location = gl.get_attrib_location(&program, "a_strength");
+
+        let u_resolution_location = gl.get_uniform_location(&program, "u_resolution");
+        let u_cell_size_location = gl.get_uniform_location(&program, "u_cell_size");
+        let u_columns_location = gl.get_uniform_location(&program, "u_columns");
+        let u_color_location = gl.get_uniform_location(&program, "u_color");
+
+        let strength_buffer = gl.create_buffer().ok_or("Failed to create buffer")?;
+        gl.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(&strength_buffer));
+
+        let vao = gl
+            .create_vertex_array()
+            .ok_or("Could not create vertex array object")?;
+        gl.bind_vertex_array(Some(&vao));
+
+        gl.vertex_attrib_pointer_with_i32(
+            a_strength_location as u32,
+            1,
+            WebGl2RenderingContext::FLOAT,
+            false,
+            0,
+            0,
+        );
+        gl.enable_vertex_attrib_array(a_strength_location as u32);
+
+        Ok(PheromoneRenderer {
+            program,
+            u_resolution_location,
+            u_cell_size_location,
+            u_columns_location,
+            u_color_location,
+            strength_buffer,
+            vao,
+        })
+    }
+
+    pub fn render(&self, gl: &WebGl2RenderingContext, simulation: &Simulation) {
+        gl.enable(WebGl2RenderingContext::BLEND);
+        gl.blend_func(
+            WebGl2RenderingContext::SRC_ALPHA,
+            WebGl2RenderingContext::ONE_MINUS_SRC_ALPHA,
+        );
+
+        for colony in 0..simulation.pheromones.colonies() {
+            for channel in PheromoneChannel::ALL {
+                self.render_channel(gl, simulation, colony, channel);
+            }
+        }
+
+        gl.disable(WebGl2RenderingContext::BLEND);
+    }
+
+    fn render_channel(
+        &self,
+        gl: &WebGl2RenderingContext,
+        simulation: &Simulation,
+        colony: usize,
+        channel: PheromoneChannel,
+    ) {
+        let field = &simulation.pheromones;
+        let values = field.layer(colony, channel);
+
+        gl.use_program(Some(&self.program));
+        gl.bind_vertex_array(Some(&self.vao));
+
+        gl.uniform4fv_with_f32_array(
+            self.u_color_location.as_ref(),
+            &tint(channel.color(), colony),
+        );
+        gl.uniform2f(
+            self.u_resolution_location.as_ref(),
+            simulation.width,
+            simulation.height,
+        );
+        gl.uniform1f(self.u_cell_size_location.as_ref(), field.cell_size);
+        gl.uniform1i(self.u_columns_location.as_ref(), field.columns as i32);
+
+        gl.bind_buffer(
+            WebGl2RenderingContext::ARRAY_BUFFER,
+            Some(&self.strength_buffer),
+        );
+
+        gl.buffer_data_with_array_buffer_view(
+            WebGl2RenderingContext::ARRAY_BUFFER,
+            &float32_array_view(values),
+            WebGl2RenderingContext::DYNAMIC_DRAW,
+        );
+
+        draw_points(gl, values.len() as i32);
+    }
+}
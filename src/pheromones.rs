@@ -1,174 +1,279 @@
-use crate::consts::{PHEROMONE_COLOR, PHEROMONE_SIZE, ANT_COUNT};
-use crate::functions::{compile_shader, draw_points, link_program};
-use wasm_bindgen::JsCast;
-use wasm_bindgen::JsValue;
-use web_sys::{WebGlBuffer, console};
-use web_sys::WebGlVertexArrayObject;
-use web_sys::{WebGl2RenderingContext, WebGlProgram, WebGlUniformLocation};
-
-pub struct PheromoneRenderer {
-    pheromones: Vec<f32>,
-    program: WebGlProgram,
-    width: f32,
-    height: f32,
-    // a_position_location: i32,
-    u_resolution_location: Option<WebGlUniformLocation>,
-    u_pheromone_size_location: Option<WebGlUniformLocation>,
-    u_color_location: Option<WebGlUniformLocation>,
-    position_buffer: WebGlBuffer,
-    positions_array_buf_view: js_sys::Float32Array,
-    vertex_count: i32,
-    vao: WebGlVertexArrayObject,
-}
-
-impl PheromoneRenderer {
-    pub fn new(gl: &WebGl2RenderingContext, width: f32, height: f32) -> Result<Self, JsValue> {
-        let vertex_shader = compile_shader(
-            &gl,
-            WebGl2RenderingContext::VERTEX_SHADER,
-            r##"#version 300 es
-
-            in vec3 a_position;
-            uniform vec2 u_resolution;
-            uniform float u_pheromone_size;
-            uniform vec4 u_color;
-            out vec4 v_color;
-
-            void main() {
-                vec2 clip_space = 2.0 * a_position.xy / u_resolution - 1.0;
-                gl_Position = vec4(clip_space * vec2(1, -1), 0, 1);
-                gl_PointSize = u_pheromone_size;
-                v_color = vec4(u_color.rgb * a_position.z, 1.0);
-            }
-            "##,
-        )
-        .expect("Error creating vertex shader");
-
-        let fragment_shader = compile_shader(
-            &gl,
-            WebGl2RenderingContext::FRAGMENT_SHADER,
-            r##"#version 300 es
-            
-            precision highp float;
-            
-            in vec4 v_color;
-            out vec4 out_color;
-
-            void main() {
-                out_color = v_color;
-            }
-            "##,
-        )
-        .expect("Error creating fragment shader");
-
-        let pheromones: Vec<f32> = vec![-1.0; ANT_COUNT * 3];
-
-        let program = link_program(&gl, &vertex_shader, &fragment_shader)?;
-
-        let a_position_location = gl.get_attrib_location(&program, "a_position");
-
-        let u_resolution_location = gl.get_uniform_location(&program, "u_resolution");
-        let u_pheromone_size_location = gl.get_uniform_location(&program, "u_pheromone_size");
-        let u_color_location = gl.get_uniform_location(&program, "u_color");
-
-        let position_buffer = gl.create_buffer().ok_or("Failed to create buffer")?;
-        gl.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(&position_buffer));
-
-        let location: u32 = pheromones.as_ptr() as u32 / 4;
-        let next_location = location + pheromones.len() as u32;
-
-        let memory_buffer = wasm_bindgen::memory()
-            .dyn_into::<js_sys::WebAssembly::Memory>()
-            .unwrap()
-            .buffer();
-
-        let positions_array_buf_view =
-            js_sys::Float32Array::new(&memory_buffer).subarray(location, next_location);
-
-        gl.buffer_data_with_array_buffer_view(
-            WebGl2RenderingContext::ARRAY_BUFFER,
-            &positions_array_buf_view,
-            WebGl2RenderingContext::DYNAMIC_DRAW,
-        );
-
-        let vao = gl
-            .create_vertex_array()
-            .ok_or("Could not create vertex array object")?;
-        gl.bind_vertex_array(Some(&vao));
-
-        gl.vertex_attrib_pointer_with_i32(
-            a_position_location as u32,
-            3,
-            WebGl2RenderingContext::FLOAT,
-            false,
-            0,
-            0,
-        );
-        gl.enable_vertex_attrib_array(a_position_location as u32);
-
-        let vertex_count = (pheromones.len() / 3) as i32;
-
-        Ok(PheromoneRenderer {
-            pheromones,
-            width,
-            height,
-            program,
-            // a_position_location,
-            u_resolution_location,
-            u_pheromone_size_location,
-            u_color_location,
-            position_buffer,
-            positions_array_buf_view,
-            vertex_count,
-            vao,
-        })
-    }
-
-    pub fn render(&mut self, gl: &WebGl2RenderingContext) {
-        gl.use_program(Some(&self.program));
-        gl.bind_vertex_array(Some(&self.vao));
-
-        gl.uniform4fv_with_f32_array(self.u_color_location.as_ref(), PHEROMONE_COLOR);
-        gl.uniform2f(self.u_resolution_location.as_ref(), self.width, self.height);
-        gl.uniform1f(self.u_pheromone_size_location.as_ref(), PHEROMONE_SIZE);
-
-        gl.bind_buffer(
-            WebGl2RenderingContext::ARRAY_BUFFER,
-            Some(&self.position_buffer),
-        );
-
-        gl.buffer_data_with_array_buffer_view(
-            WebGl2RenderingContext::ARRAY_BUFFER,
-            &self.positions_array_buf_view,
-            WebGl2RenderingContext::STATIC_DRAW,
-        );
-        
-        draw_points(&gl, self.vertex_count);
-        
-        for idx in (0..self.pheromones.len()).step_by(3) {
-            let (part1, part2) = self.pheromones.split_at_mut(idx + 1);
-            let x = part1.last_mut().expect("Error indexing vector");
-            let (part3, part4) = part2.split_at_mut(1);
-            let y = part3.last_mut().expect("Error indexing vector");
-            let str = part4.first_mut().expect("Error indexing vector");
-            *str -= 0.003;
-            if *str <= 0.0 {
-                *x = -1.0;
-                *y = -1.0;
-                *str = -1.0;
-            }
-        }
-    }
-
-    pub fn add_pheromone(&mut self, pos: (f32, f32)) {
-        let result = self.pheromones.iter().step_by(3).position(|&x| x <= 0.0);
-        match result {
-            Some(idx) => {
-                self.pheromones[idx * 3] = pos.0;
-                self.pheromones[idx * 3 + 1] = pos.1;
-                self.pheromones[idx * 3 + 2] = 1.0;
-            },
-            None => ()
-        }
-    }
-}
+use crate::config::{DiffusionKernel, EvaporationKernel};
+use crate::consts::{FOOD_PHEROMONE_COLOR, HOME_PHEROMONE_COLOR};
+use crate::spatial::within_arc;
+use serde::{Deserialize, Serialize};
+
+/// Food trails are laid by ants returning with food and followed by searching
+/// ants; home trails are laid by searching ants and followed back to the nest.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PheromoneChannel {
+    Food,
+    Home,
+}
+
+impl PheromoneChannel {
+    pub const ALL: [PheromoneChannel; 2] = [PheromoneChannel::Food, PheromoneChannel::Home];
+
+    pub fn color(&self) -> &'static [f32; 4] {
+        match self {
+            PheromoneChannel::Food => FOOD_PHEROMONE_COLOR,
+            PheromoneChannel::Home => HOME_PHEROMONE_COLOR,
+        }
+    }
+}
+
+/// Scalar pheromone strength per colony, per channel and per cell of a
+/// `cell_size` grid covering the world.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PheromoneField {
+    pub columns: usize,
+    pub rows: usize,
+    pub cell_size: f32,
+    layers: Vec<[Vec<f32>; 2]>,
+    #[serde(skip)]
+    scratch: Vec<f32>,
+}
+
+impl PheromoneField {
+    pub fn new(width: f32, height: f32, cell_size: f32, colonies: usize) -> Self {
+        let columns = (width / cell_size).ceil() as usize;
+        let rows = (height / cell_size).ceil() as usize;
+        PheromoneField {
+            columns,
+            rows,
+            cell_size,
+            layers: vec![[vec![0.0; columns * rows], vec![0.0; columns * rows]]; colonies],
+            scratch: vec![0.0; columns * rows],
+        }
+    }
+
+    pub fn colonies(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Whether every layer covers exactly `columns * rows` cells.
+    pub fn is_consistent(&self) -> bool {
+        let cells = self.columns * self.rows;
+        self.layers
+            .iter()
+            .all(|layers| layers.iter().all(|layer| layer.len() == cells))
+    }
+
+    pub fn cell_index(&self, pos: (f32, f32)) -> Option<usize> {
+        if pos.0 < 0.0 || pos.1 < 0.0 {
+            return None;
+        }
+        let column = (pos.0 / self.cell_size) as usize;
+        let row = (pos.1 / self.cell_size) as usize;
+        if column >= self.columns || row >= self.rows {
+            return None;
+        }
+        Some(row * self.columns + column)
+    }
+
+    pub fn layer(&self, colony: usize, channel: PheromoneChannel) -> &[f32] {
+        &self.layers[colony][channel as usize]
+    }
+
+    pub fn add_pheromone(
+        &mut self,
+        colony: usize,
+        channel: PheromoneChannel,
+        pos: (f32, f32),
+        amount: f32,
+    ) {
+        if let Some(idx) = self.cell_index(pos) {
+            self.layers[colony][channel as usize][idx] += amount;
+        }
+    }
+
+    pub fn sample(&self, colony: usize, channel: PheromoneChannel, pos: (f32, f32)) -> f32 {
+        self.cell_index(pos)
+            .map_or(0.0, |idx| self.layers[colony][channel as usize][idx])
+    }
+
+    /// Returns the centres and strengths of non-empty cells within `radius` of
+    /// `pos` and `arc / 2` of `dir`, visiting only the cells in range.
+    pub fn cells_within(
+        &self,
+        colony: usize,
+        channel: PheromoneChannel,
+        pos: (f32, f32),
+        radius: f32,
+        dir: f32,
+        arc: f32,
+    ) -> Vec<((f32, f32), f32)> {
+        let values = &self.layers[colony][channel as usize];
+        let min_column = ((pos.0 - radius) / self.cell_size).max(0.0) as usize;
+        let min_row = ((pos.1 - radius) / self.cell_size).max(0.0) as usize;
+        let max_column =
+            (((pos.0 + radius) / self.cell_size).max(0.0) as usize).min(self.columns - 1);
+        let max_row = (((pos.1 + radius) / self.cell_size).max(0.0) as usize).min(self.rows - 1);
+        let mut result = Vec::new();
+        for row in min_row..=max_row {
+            for column in min_column..=max_column {
+                let strength = values[row * self.columns + column];
+                if strength <= 0.0 {
+                    continue;
+                }
+                let center = (
+                    (column as f32 + 0.5) * self.cell_size,
+                    (row as f32 + 0.5) * self.cell_size,
+                );
+                if within_arc(pos, radius, dir, arc, center) {
+                    result.push((center, strength));
+                }
+            }
+        }
+        result
+    }
+
+    pub fn evaporate(
+        &mut self,
+        colony: usize,
+        channel: PheromoneChannel,
+        kernel: EvaporationKernel,
+        rate: f32,
+    ) {
+        let values = &mut self.layers[colony][channel as usize];
+        match kernel {
+            EvaporationKernel::Linear => {
+                for value in values.iter_mut() {
+                    *value = (*value - rate).max(0.0);
+                }
+            }
+            EvaporationKernel::Exponential => {
+                let keep = (1.0 - rate).clamp(0.0, 1.0);
+                for value in values.iter_mut() {
+                    *value *= keep;
+                }
+            }
+        }
+    }
+
+    /// Moves `rate` of each cell's strength towards the average of its
+    /// neighbours under the given kernel. Strength only flows between
+    /// in-bounds cells, so the layer's total is conserved.
+    pub fn diffuse(
+        &mut self,
+        colony: usize,
+        channel: PheromoneChannel,
+        kernel: DiffusionKernel,
+        rate: f32,
+    ) {
+        let values = &mut self.layers[colony][channel as usize];
+        // Snapshots don't carry the scratch buffer.
+        self.scratch.resize(values.len(), 0.0);
+        let offsets: &[(isize, isize)] = match kernel {
+            DiffusionKernel::None => return,
+            DiffusionKernel::Cross => &[(-1, 0), (1, 0), (0, -1), (0, 1)],
+            DiffusionKernel::Box => &[
+                (-1, -1),
+                (0, -1),
+                (1, -1),
+                (-1, 0),
+                (1, 0),
+                (-1, 1),
+                (0, 1),
+                (1, 1),
+            ],
+        };
+        // Each cell hands this much of its strength to every neighbour.
+        let share = rate / offsets.len() as f32;
+        for row in 0..self.rows {
+            for column in 0..self.columns {
+                let mut inflow = 0.0;
+                let mut count = 0;
+                for (d_x, d_y) in offsets {
+                    let n_column = column as isize + d_x;
+                    let n_row = row as isize + d_y;
+                    if n_column < 0
+                        || n_row < 0
+                        || n_column >= self.columns as isize
+                        || n_row >= self.rows as isize
+                    {
+                        continue;
+                    }
+                    inflow += values[n_row as usize * self.columns + n_column as usize];
+                    count += 1;
+                }
+                let idx = row * self.columns + column;
+                let value = values[idx];
+                self.scratch[idx] = value + (inflow - value * count as f32) * share;
+            }
+        }
+        std::mem::swap(values, &mut self.scratch);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(columns: usize, rows: usize) -> PheromoneField {
+        PheromoneField::new(columns as f32 * 2.0, rows as f32 * 2.0, 2.0, 1)
+    }
+
+    fn total(field: &PheromoneField) -> f32 {
+        field.layer(0, PheromoneChannel::Food).iter().sum()
+    }
+
+    #[test]
+    fn linear_decay_subtracts_down_to_zero() {
+        let mut field = field(2, 1);
+        field.add_pheromone(0, PheromoneChannel::Food, (1.0, 1.0), 1.0);
+        field.add_pheromone(0, PheromoneChannel::Food, (3.0, 1.0), 0.1);
+        field.evaporate(0, PheromoneChannel::Food, EvaporationKernel::Linear, 0.25);
+        assert_eq!(field.layer(0, PheromoneChannel::Food), [0.75, 0.0]);
+        assert_eq!(field.layer(0, PheromoneChannel::Home), [0.0, 0.0]);
+    }
+
+    #[test]
+    fn exponential_decay_scales() {
+        let mut field = field(2, 1);
+        field.add_pheromone(0, PheromoneChannel::Food, (1.0, 1.0), 1.0);
+        field.add_pheromone(0, PheromoneChannel::Food, (3.0, 1.0), 0.1);
+        field.evaporate(
+            0,
+            PheromoneChannel::Food,
+            EvaporationKernel::Exponential,
+            0.25,
+        );
+        assert_eq!(field.layer(0, PheromoneChannel::Food), [0.75, 0.075]);
+    }
+
+    #[test]
+    fn diffusion_spreads_and_conserves_mass() {
+        for kernel in [DiffusionKernel::Cross, DiffusionKernel::Box] {
+            let mut field = field(5, 4);
+            // A corner cell, where fewer neighbours are in bounds.
+            field.add_pheromone(0, PheromoneChannel::Food, (1.0, 1.0), 4.0);
+            field.add_pheromone(0, PheromoneChannel::Food, (5.0, 5.0), 2.0);
+            for _ in 0..20 {
+                field.diffuse(0, PheromoneChannel::Food, kernel, 0.5);
+                assert!((total(&field) - 6.0).abs() < 1e-4, "{:?}", kernel);
+            }
+            let layer = field.layer(0, PheromoneChannel::Food);
+            assert!(layer.iter().all(|&value| value > 0.0), "{:?}", kernel);
+            assert!(layer[0] < 4.0);
+        }
+    }
+
+    #[test]
+    fn diffusion_without_neighbours_keeps_values() {
+        for (columns, rows, kernel) in [
+            (1, 1, DiffusionKernel::Box),
+            (1, 1, DiffusionKernel::Cross),
+            (1, 3, DiffusionKernel::Cross),
+        ] {
+            let mut field = field(columns, rows);
+            field.add_pheromone(0, PheromoneChannel::Food, (1.0, 1.0), 1.0);
+            field.diffuse(0, PheromoneChannel::Food, kernel, 0.5);
+            assert!(field
+                .layer(0, PheromoneChannel::Food)
+                .iter()
+                .all(|value| value.is_finite()));
+            assert!((total(&field) - 1.0).abs() < 1e-6);
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use crate::config::SimConfig;
+use crate::grid::BrushKind;
+use serde::{Deserialize, Serialize};
+
+/// Bumped whenever the replay layout changes so stale logs are rejected.
+pub const REPLAY_VERSION: u32 = 1;
+
+/// A change made to a running simulation from outside of `step`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Intervention {
+    Paint {
+        pos: (f32, f32),
+        brush: BrushKind,
+        radius: u32,
+    },
+    SetConfig {
+        config: SimConfig,
+    },
+    /// An ASCII map, see `map.rs`.
+    LoadMap {
+        map: String,
+    },
+    Resize {
+        width: f32,
+        height: f32,
+    },
+}
+
+/// An intervention applied after `step` steps had run.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReplayEvent {
+    pub step: u64,
+    pub intervention: Intervention,
+}
+
+/// The starting size, config and grid of a run plus every intervention made
+/// during it, in order. Together with the seed in `config` this reproduces the
+/// run exactly.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReplayLog {
+    pub version: u32,
+    pub width: f32,
+    pub height: f32,
+    pub config: SimConfig,
+    /// The grid when recording started, so loaded maps and painted cells from
+    /// before the recording are kept.
+    pub grid: Vec<f32>,
+    pub events: Vec<ReplayEvent>,
+}
+
+impl ReplayLog {
+    pub fn new(width: f32, height: f32, config: SimConfig, grid: Vec<f32>) -> Self {
+        ReplayLog {
+            version: REPLAY_VERSION,
+            width,
+            height,
+            config,
+            grid,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, step: u64, intervention: Intervention) {
+        self.events.push(ReplayEvent { step, intervention });
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Error serializing replay")
+    }
+
+    pub fn from_json(json: &str) -> Result<ReplayLog, String> {
+        let log: ReplayLog =
+            serde_json::from_str(json).map_err(|e| format!("Invalid replay JSON: {}", e))?;
+        if log.version != REPLAY_VERSION {
+            return Err(format!(
+                "Replay version {} is not supported, expected {}",
+                log.version, REPLAY_VERSION
+            ));
+        }
+        if log
+            .events
+            .windows(2)
+            .any(|pair| pair[0].step > pair[1].step)
+        {
+            return Err(String::from("Replay events are out of order"));
+        }
+        Ok(log)
+    }
+}
@@ -0,0 +1,1693 @@
+use crate::{
+    angle,
+    caste::Caste,
+    collision::{reflect, sweep},
+    colony::Colony,
+    config::{Boundary, Homing, SimConfig},
+    consts::{GRID_SIZE, MAX_BOUNCES, PI},
+    flow_field::FlowField,
+    functions::{
+        check_world_size, dir_to_nest, get_resource_in_cell, grid_dimensions, initialize_ants,
+        initialize_grid, nest_positions, next_ant_position,
+    },
+    grid::{BrushKind, CellCoord, GridResource},
+    map::{Map, MapError},
+    pheromones::{PheromoneChannel, PheromoneField},
+    replay::{Intervention, ReplayLog},
+    snapshot::{Snapshot, SNAPSHOT_VERSION},
+    spatial::SpatialHash,
+    stats::ColonyStats,
+};
+use rand::prelude::*;
+use rand_xoshiro::rand_core::SeedableRng;
+use rand_xoshiro::Xoshiro256Plus;
+
+/// A cell an ant touched without entering.
+type Contact = (GridResource, CellCoord);
+
+pub struct Simulation {
+    pub config: SimConfig,
+    pub width: f32,
+    pub height: f32,
+    pub ants: Vec<f32>,
+    pub dirs: Vec<f32>,
+    pub has_food: Vec<bool>,
+    pub ant_colonies: Vec<usize>,
+    pub trip_steps: Vec<u32>,
+    pub trip_lengths: Vec<f32>,
+    pub energy: Vec<f32>,
+    /// Each ant's remembered `(x, y)` offset from its nest, integrated from
+    /// its own movement for path integration homing.
+    pub home_vectors: Vec<f32>,
+    pub castes: Vec<Caste>,
+    pub grid: Vec<f32>,
+    pub grid_version: u64,
+    pub colonies: Vec<Colony>,
+    pub pheromones: PheromoneField,
+    pub steps: u64,
+    /// Ant positions bucketed for `ants_within`, kept up to date as ants move,
+    /// spawn and die.
+    ant_index: SpatialHash,
+    /// Built on demand for flow field homing and dropped whenever the map changes.
+    flow_field: Option<FlowField>,
+    pheromone_timer: usize,
+    rng: Xoshiro256Plus,
+    recording: Option<ReplayLog>,
+    replay: Option<ReplayLog>,
+    replay_cursor: usize,
+}
+
+impl Simulation {
+    pub fn new(width: f32, height: f32, config: SimConfig) -> Result<Self, String> {
+        check_world_size(width, height)?;
+        config.validate()?;
+        let mut rng = Xoshiro256Plus::seed_from_u64(config.seed);
+
+        let nest_coords = (
+            (width / GRID_SIZE) as usize / 2,
+            (height / GRID_SIZE) as usize / 2,
+        );
+
+        let grid = initialize_grid(width, height, nest_coords, config.food_per_cell);
+        let colonies: Vec<Colony> = nest_positions(&grid, width)
+            .into_iter()
+            .map(|nest_pos| Colony::new(nest_pos.expect("Default grid has no nest")))
+            .collect();
+        let nest_positions: Vec<(f32, f32)> = colonies.iter().map(|c| c.nest_pos).collect();
+
+        let (ants, dirs, has_food, ant_colonies) =
+            initialize_ants(&mut rng, &nest_positions, 0, config.ant_count);
+        let castes = (0..config.ant_count)
+            .map(|_| Caste::pick(&config, &mut rng))
+            .collect();
+
+        let pheromones =
+            PheromoneField::new(width, height, config.pheromone_cell_size, colonies.len());
+        let ant_index = Simulation::ant_index(width, height, &config, &ants);
+
+        Ok(Simulation {
+            config,
+            width,
+            height,
+            ants,
+            dirs,
+            trip_steps: vec![0; config.ant_count],
+            trip_lengths: vec![0.0; config.ant_count],
+            energy: vec![config.max_energy; config.ant_count],
+            home_vectors: vec![0.0; config.ant_count * 2],
+            castes,
+            has_food,
+            ant_colonies,
+            grid,
+            grid_version: 0,
+            colonies,
+            pheromones,
+            steps: 0,
+            ant_index,
+            flow_field: None,
+            pheromone_timer: config.pheromone_timer,
+            rng,
+            recording: None,
+            replay: None,
+            replay_cursor: 0,
+        })
+    }
+
+    /// Applies `config` to the running simulation. A changed seed restarts the
+    /// random stream from that seed, and a changed `ant_count` adds or removes
+    /// ants to match it.
+    pub fn set_config(&mut self, config: SimConfig) {
+        if config.seed != self.config.seed {
+            self.rng = Xoshiro256Plus::seed_from_u64(config.seed);
+        }
+        if config.ant_count != self.config.ant_count {
+            self.set_ant_count(&config);
+        }
+        if config.boundary != self.config.boundary {
+            self.flow_field = None;
+        }
+        if config.pheromone_cell_size != self.pheromones.cell_size {
+            self.pheromones = PheromoneField::new(
+                self.width,
+                self.height,
+                config.pheromone_cell_size,
+                self.colonies.len(),
+            );
+        }
+        self.pheromone_timer = self.pheromone_timer.min(config.pheromone_timer);
+        let view_radius_changed = config.view_radius != self.config.view_radius;
+        self.config = config;
+        if view_radius_changed {
+            self.rebuild_ant_index();
+        }
+    }
+
+    fn set_ant_count(&mut self, config: &SimConfig) {
+        let ant_count = config.ant_count;
+        let current_count = self.dirs.len();
+        if ant_count > current_count {
+            let nest_positions = self.nest_positions();
+            let (ants, dirs, has_food, ant_colonies) = initialize_ants(
+                &mut self.rng,
+                &nest_positions,
+                current_count,
+                ant_count - current_count,
+            );
+            self.ants.extend(ants);
+            self.dirs.extend(dirs);
+            self.has_food.extend(has_food);
+            self.ant_colonies.extend(ant_colonies);
+            for _ in current_count..ant_count {
+                self.castes.push(Caste::pick(config, &mut self.rng));
+            }
+        } else {
+            self.ants.truncate(ant_count * 2);
+            self.dirs.truncate(ant_count);
+            self.has_food.truncate(ant_count);
+            self.ant_colonies.truncate(ant_count);
+            self.castes.truncate(ant_count);
+        }
+        self.trip_steps.resize(ant_count, 0);
+        self.trip_lengths.resize(ant_count, 0.0);
+        self.energy.resize(ant_count, config.max_energy);
+        self.home_vectors.resize(ant_count * 2, 0.0);
+        self.rebuild_ant_index();
+    }
+
+    /// Replaces the grid with `map` and restarts every colony at its nest, with
+    /// the random stream reseeded from the config.
+    pub fn load_map(&mut self, map: &Map) -> Result<(), MapError> {
+        let (columns, rows) = grid_dimensions(self.width, self.height);
+        map.check_bounds(columns, rows)?;
+
+        let mut grid = vec![0.0; columns * rows * 2];
+        for (i, resource) in map.cells.iter().enumerate() {
+            let idx = ((i / map.columns) * columns + i % map.columns) * 2;
+            (grid[idx], grid[idx + 1]) = resource.cell_values(self.config.food_per_cell);
+        }
+        // Checked before anything changes, since `Map`'s fields are public and
+        // it may not have come through the parser.
+        let nest_positions = nest_positions(&grid, self.width)
+            .into_iter()
+            .collect::<Option<Vec<_>>>()
+            .filter(|positions| !positions.is_empty())
+            .ok_or(MapError::MissingNest)?;
+        self.grid = grid;
+        self.grid_version += 1;
+        self.flow_field = None;
+        self.colonies = nest_positions.iter().copied().map(Colony::new).collect();
+
+        let ant_count = self.config.ant_count;
+        self.rng = Xoshiro256Plus::seed_from_u64(self.config.seed);
+        self.pheromone_timer = self.config.pheromone_timer;
+        (self.ants, self.dirs, self.has_food, self.ant_colonies) =
+            initialize_ants(&mut self.rng, &nest_positions, 0, ant_count);
+        self.trip_steps = vec![0; ant_count];
+        self.trip_lengths = vec![0.0; ant_count];
+        self.energy = vec![self.config.max_energy; ant_count];
+        self.home_vectors = vec![0.0; ant_count * 2];
+        self.castes = (0..ant_count)
+            .map(|_| Caste::pick(&self.config, &mut self.rng))
+            .collect();
+        self.pheromones = PheromoneField::new(
+            self.width,
+            self.height,
+            self.config.pheromone_cell_size,
+            self.colonies.len(),
+        );
+        self.rebuild_ant_index();
+        Ok(())
+    }
+
+    /// Resizes the world, keeping the overlapping part of the grid. Ants left
+    /// outside the new grid are returned to their nest and trails are cleared.
+    /// Nests cropped away entirely are rebuilt as close to where they were as
+    /// the new bounds allow.
+    pub fn resize(&mut self, width: f32, height: f32) -> Result<(), String> {
+        check_world_size(width, height)?;
+        let (old_columns, old_rows) = grid_dimensions(self.width, self.height);
+        let (columns, rows) = grid_dimensions(width, height);
+        let mut grid = vec![0.0; columns * rows * 2];
+        for row in 0..rows.min(old_rows) {
+            for column in 0..columns.min(old_columns) {
+                let old_idx = (row * old_columns + column) * 2;
+                let idx = (row * columns + column) * 2;
+                grid[idx..idx + 2].copy_from_slice(&self.grid[old_idx..old_idx + 2]);
+            }
+        }
+        let kept_nests = nest_positions(&grid, width);
+        for (colony, state) in self.colonies.iter().enumerate() {
+            if kept_nests.get(colony).copied().flatten().is_some() {
+                continue;
+            }
+            let nest_column = ((state.nest_pos.0 / GRID_SIZE) as usize).min(columns - 1);
+            let nest_row = ((state.nest_pos.1 / GRID_SIZE) as usize).min(rows - 1);
+            for row in nest_row.saturating_sub(1)..=nest_row {
+                for column in nest_column.saturating_sub(1)..=nest_column {
+                    let idx = (row * columns + column) * 2;
+                    (grid[idx], grid[idx + 1]) = GridResource::Nest(colony).cell_values(0.0);
+                }
+            }
+        }
+        let nest_positions = nest_positions(&grid, width);
+        for (colony, state) in self.colonies.iter_mut().enumerate() {
+            state.nest_pos = nest_positions[colony].expect("Nest was just placed");
+        }
+
+        self.width = width;
+        self.height = height;
+        self.grid = grid;
+        self.grid_version += 1;
+        self.flow_field = None;
+        self.return_walled_ants();
+        self.pheromones = PheromoneField::new(
+            width,
+            height,
+            self.config.pheromone_cell_size,
+            self.colonies.len(),
+        );
+        self.rebuild_ant_index();
+        Ok(())
+    }
+
+    /// Paints every non-nest cell within `radius` cells of `pos`.
+    pub fn paint(&mut self, pos: (f32, f32), brush: BrushKind, radius: u32) {
+        let (columns, rows) = grid_dimensions(self.width, self.height);
+        let Some(center) = CellCoord::from_pos(pos, columns, rows) else {
+            return;
+        };
+        // Any larger radius already covers the whole grid.
+        let radius = (radius as usize).min(columns + rows) as isize;
+        let (center_column, center_row) = (center.column() as isize, center.row() as isize);
+        let values = brush.resource().cell_values(self.config.food_per_cell);
+        for row in (center_row - radius).max(0)..=(center_row + radius).min(rows as isize - 1) {
+            for column in
+                (center_column - radius).max(0)..=(center_column + radius).min(columns as isize - 1)
+            {
+                let (d_x, d_y) = (column - center_column, row - center_row);
+                if d_x * d_x + d_y * d_y > radius * radius {
+                    continue;
+                }
+                let Some(cell) = CellCoord::new(column, row, columns, rows) else {
+                    continue;
+                };
+                let idx = cell.idx(columns);
+                if self.grid[idx] as usize == 1 {
+                    continue;
+                }
+                (self.grid[idx], self.grid[idx + 1]) = values;
+            }
+        }
+        self.grid_version += 1;
+        self.flow_field = None;
+        if brush.resource() == GridResource::Wall {
+            self.return_walled_ants();
+        }
+    }
+
+    /// Sends ants inside walls or outside the grid back to their nest.
+    fn return_walled_ants(&mut self) {
+        let (columns, rows) = grid_dimensions(self.width, self.height);
+        for (idx, &colony) in self.ant_colonies.iter().enumerate() {
+            let pos = (self.ants[idx * 2], self.ants[idx * 2 + 1]);
+            if CellCoord::from_pos(pos, columns, rows).is_none()
+                || self.resource_at(pos) == GridResource::Wall
+            {
+                let nest_pos = self.colonies[colony].nest_pos;
+                (self.ants[idx * 2], self.ants[idx * 2 + 1]) = nest_pos;
+                self.home_vectors[idx * 2..idx * 2 + 2].fill(0.0);
+                self.ant_index.update(idx, nest_pos);
+            }
+        }
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            version: SNAPSHOT_VERSION,
+            config: self.config,
+            width: self.width,
+            height: self.height,
+            ants: self.ants.clone(),
+            dirs: self.dirs.clone(),
+            has_food: self.has_food.clone(),
+            ant_colonies: self.ant_colonies.clone(),
+            trip_steps: self.trip_steps.clone(),
+            trip_lengths: self.trip_lengths.clone(),
+            energy: self.energy.clone(),
+            home_vectors: self.home_vectors.clone(),
+            castes: self.castes.clone(),
+            grid: self.grid.clone(),
+            colonies: self.colonies.clone(),
+            pheromones: self.pheromones.clone(),
+            steps: self.steps,
+            pheromone_timer: self.pheromone_timer,
+            rng: self.rng.clone(),
+        }
+    }
+
+    /// Replaces the whole simulation state with `snapshot`, including its
+    /// world size, after checking that its arrays agree with each other. Any
+    /// recording or replay is stopped since it can't be reproduced from a seed.
+    pub fn restore(&mut self, snapshot: Snapshot) -> Result<(), String> {
+        check_world_size(snapshot.width, snapshot.height)?;
+        snapshot.config.validate()?;
+        let ant_count = snapshot.dirs.len();
+        let (columns, rows) = grid_dimensions(snapshot.width, snapshot.height);
+        if snapshot.ants.len() != ant_count * 2
+            || snapshot.has_food.len() != ant_count
+            || snapshot.ant_colonies.len() != ant_count
+            || snapshot.trip_steps.len() != ant_count
+            || snapshot.trip_lengths.len() != ant_count
+            || snapshot.energy.len() != ant_count
+            || snapshot.home_vectors.len() != ant_count * 2
+            || snapshot.castes.len() != ant_count
+        {
+            return Err(String::from("Snapshot ant arrays have mismatched lengths"));
+        }
+        if snapshot.grid.len() != columns * rows * 2 {
+            return Err(String::from("Snapshot grid does not match its size"));
+        }
+        if snapshot.colonies.is_empty()
+            || snapshot.pheromones.colonies() != snapshot.colonies.len()
+            || snapshot.pheromones.cell_size != snapshot.config.pheromone_cell_size
+            || !snapshot.pheromones.is_consistent()
+            || snapshot
+                .ant_colonies
+                .iter()
+                .any(|&colony| colony >= snapshot.colonies.len())
+        {
+            return Err(String::from("Snapshot colonies are inconsistent"));
+        }
+
+        self.config = snapshot.config;
+        self.width = snapshot.width;
+        self.height = snapshot.height;
+        self.ants = snapshot.ants;
+        self.dirs = snapshot.dirs;
+        self.has_food = snapshot.has_food;
+        self.ant_colonies = snapshot.ant_colonies;
+        self.trip_steps = snapshot.trip_steps;
+        self.trip_lengths = snapshot.trip_lengths;
+        self.energy = snapshot.energy;
+        self.home_vectors = snapshot.home_vectors;
+        self.castes = snapshot.castes;
+        self.grid = snapshot.grid;
+        self.grid_version += 1;
+        self.flow_field = None;
+        self.colonies = snapshot.colonies;
+        self.pheromones = snapshot.pheromones;
+        self.steps = snapshot.steps;
+        self.pheromone_timer = snapshot.pheromone_timer;
+        self.rng = snapshot.rng;
+        self.rebuild_ant_index();
+        self.recording = None;
+        self.replay = None;
+        Ok(())
+    }
+
+    pub fn to_map(&self) -> Map {
+        let (columns, rows) = grid_dimensions(self.width, self.height);
+        let cells = (0..rows)
+            .flat_map(|row| (0..columns).map(move |column| (column, row)))
+            .map(|(column, row)| {
+                let cell = CellCoord::new(column as isize, row as isize, columns, rows)
+                    .expect("Cell is inside the grid");
+                get_resource_in_cell(&self.grid, columns, cell)
+            })
+            .collect();
+        Map {
+            columns,
+            rows,
+            cells,
+        }
+    }
+
+    /// Applies `intervention` and logs it if a recording is running. User
+    /// interventions are refused during a replay, which would otherwise stop
+    /// matching the run it came from.
+    pub fn apply(&mut self, intervention: Intervention) -> Result<(), String> {
+        if self.is_replaying() {
+            return Err(String::from(
+                "The simulation can't be changed during a replay",
+            ));
+        }
+        self.intervene(intervention)
+    }
+
+    fn intervene(&mut self, intervention: Intervention) -> Result<(), String> {
+        match &intervention {
+            Intervention::Paint { pos, brush, radius } => self.paint(*pos, *brush, *radius),
+            Intervention::SetConfig { config } => {
+                config.validate()?;
+                self.set_config(*config);
+            }
+            Intervention::LoadMap { map } => {
+                let map = Map::parse_ascii(map).map_err(|e| e.to_string())?;
+                self.load_map(&map).map_err(|e| e.to_string())?;
+            }
+            Intervention::Resize { width, height } => self.resize(*width, *height)?,
+        }
+        if let Some(recording) = &mut self.recording {
+            recording.record(self.steps, intervention);
+        }
+        Ok(())
+    }
+
+    /// Restarts on the current size, config and grid and starts logging
+    /// interventions.
+    pub fn start_recording(&mut self) -> Result<(), String> {
+        let (width, height, config) = (self.width, self.height, self.config);
+        let grid = self.grid.clone();
+        self.reset(width, height, config)?;
+        self.restart_on_grid(&grid)?;
+        self.recording = Some(ReplayLog::new(width, height, config, grid));
+        Ok(())
+    }
+
+    pub fn stop_recording(&mut self) -> Option<ReplayLog> {
+        self.recording.take()
+    }
+
+    /// Restarts from the start of `log` and re-applies its interventions as
+    /// the simulation reaches the steps they were recorded at.
+    pub fn start_replay(&mut self, log: ReplayLog) -> Result<(), String> {
+        self.reset(log.width, log.height, log.config)?;
+        self.restart_on_grid(&log.grid)?;
+        self.replay = Some(log);
+        self.apply_replay_events();
+        Ok(())
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        self.replay.is_some()
+    }
+
+    fn reset(&mut self, width: f32, height: f32, config: SimConfig) -> Result<(), String> {
+        let grid_version = self.grid_version + 1;
+        *self = Simulation::new(width, height, config)?;
+        self.grid_version = grid_version;
+        Ok(())
+    }
+
+    /// Places the nests and ants for `grid` as `load_map` would, then keeps
+    /// its exact food amounts.
+    fn restart_on_grid(&mut self, grid: &[f32]) -> Result<(), String> {
+        if grid.len() != self.grid.len() {
+            return Err(String::from("Replay grid does not match the world size"));
+        }
+        self.grid.copy_from_slice(grid);
+        let map = self.to_map();
+        self.load_map(&map).map_err(|e| e.to_string())?;
+        self.grid.copy_from_slice(grid);
+        Ok(())
+    }
+
+    fn apply_replay_events(&mut self) {
+        while let Some(event) = self
+            .replay
+            .as_ref()
+            .and_then(|log| log.events.get(self.replay_cursor))
+            .filter(|event| event.step <= self.steps)
+            .cloned()
+        {
+            self.replay_cursor += 1;
+            // Recorded interventions succeeded when they were made, so a
+            // failure here can only come from a hand-edited log.
+            let _ = self.intervene(event.intervention);
+        }
+        if self
+            .replay
+            .as_ref()
+            .is_some_and(|log| self.replay_cursor >= log.events.len())
+        {
+            self.replay = None;
+        }
+    }
+
+    pub fn step(&mut self) {
+        self.apply_replay_events();
+        self.move_ants();
+        if self.config.lifecycle {
+            self.spawn_ants();
+        }
+        self.decay_pheromones();
+        self.steps += 1;
+    }
+
+    /// Counters summed over every colony.
+    pub fn stats(&self) -> ColonyStats {
+        let mut stats = ColonyStats {
+            step: self.steps as f64,
+            ..ColonyStats::default()
+        };
+        for colony in 0..self.colonies.len() {
+            stats.accumulate(&self.colony_stats(colony).expect("Colony exists"));
+        }
+        stats
+    }
+
+    pub fn colony_stats(&self, colony: usize) -> Option<ColonyStats> {
+        let state = self.colonies.get(colony)?;
+        Some(ColonyStats {
+            step: self.steps as f64,
+            ant_count: self.ant_colonies.iter().filter(|&&c| c == colony).count() as u32,
+            ..state.stats
+        })
+    }
+
+    fn nest_positions(&self) -> Vec<(f32, f32)> {
+        self.colonies.iter().map(|colony| colony.nest_pos).collect()
+    }
+
+    fn move_ants(&mut self) {
+        self.pheromone_timer = self.pheromone_timer.saturating_sub(1);
+
+        let (columns, rows) = grid_dimensions(self.width, self.height);
+        if self.config.homing == Homing::FlowField && self.flow_field.is_none() {
+            self.flow_field = Some(FlowField::new(
+                &self.grid,
+                columns,
+                rows,
+                self.colonies.len(),
+                self.config.boundary == Boundary::Wrap,
+            ));
+        }
+        let mut removed = Vec::new();
+        for idx in 0..self.dirs.len() {
+            let (x, y) = (self.ants[idx * 2], self.ants[idx * 2 + 1]);
+            let colony = self.ant_colonies[idx];
+            let caste = self.config.caste_params(self.castes[idx]);
+            let speed = self.config.walk_speed * caste.speed;
+            // Settle whatever the ant is standing in before it moves: food
+            // painted under it is picked up and food carried into its own nest
+            // is delivered, whichever way it is heading.
+            let (column, row) = CellCoord::unbounded((x, y));
+            let dir = match (self.resource_at((x, y)), self.cell(column, row)) {
+                (GridResource::Food, Some(cell)) if !self.has_food[idx] => {
+                    self.pick_up_food(idx, cell);
+                    angle::wrap(self.dirs[idx] + PI)
+                }
+                (GridResource::Nest(nest), _) if nest == colony && self.has_food[idx] => {
+                    self.reach_nest(idx, colony);
+                    angle::wrap(self.dirs[idx] + PI)
+                }
+                _ => angle::wrap(self.dirs[idx]),
+            };
+            let mut next_dir = dir;
+            let (follow_channel, lay_channel) = if self.has_food[idx] {
+                (PheromoneChannel::Home, PheromoneChannel::Food)
+            } else {
+                (PheromoneChannel::Food, PheromoneChannel::Home)
+            };
+            next_dir += self.sensor_turn(colony, follow_channel, (x, y), dir) * caste.sensitivity;
+            if self.has_food[idx] {
+                if let Some(nest_dir) = self.homing_dir(idx, colony, (x, y)) {
+                    next_dir = angle::lerp(next_dir, nest_dir, self.config.nest_honing_strength);
+                }
+            }
+            if self.pheromone_timer == 0 {
+                self.pheromones.add_pheromone(
+                    colony,
+                    lay_channel,
+                    (x, y),
+                    self.config.pheromone_deposit * caste.deposit,
+                );
+            }
+            next_dir +=
+                (self.rng.gen::<f32>() - 0.5) * self.config.wander_coefficient * caste.wander;
+            next_dir = angle::clamp_turn(dir, next_dir, self.config.max_turn_rate);
+            let carrying = self.has_food[idx];
+            let leaving_nest = self.resource_at((x, y)) == GridResource::Nest(colony);
+            let passable = |resource| match resource {
+                GridResource::Blank => true,
+                GridResource::Food => carrying,
+                GridResource::Nest(nest) => nest == colony && leaving_nest,
+                GridResource::Wall => false,
+            };
+            let (mut next_pos, mut next_dir, contact) =
+                self.walk((x, y), next_dir, speed, colony, passable);
+            match contact {
+                Some((GridResource::Food, cell)) => {
+                    self.pick_up_food(idx, cell);
+                    next_dir += PI;
+                }
+                Some((GridResource::Nest(_), _)) => {
+                    self.reach_nest(idx, colony);
+                    next_dir += PI;
+                }
+                _ => {}
+            }
+            let distance = ((next_pos.0 - x).powi(2) + (next_pos.1 - y).powi(2)).sqrt();
+            if self.config.homing == Homing::PathIntegration {
+                self.integrate_path(idx, (next_pos.0 - x, next_pos.1 - y));
+            }
+            if self.config.boundary == Boundary::Wrap {
+                next_pos = self.wrap_position(next_pos);
+            }
+            self.trip_steps[idx] += 1;
+            self.trip_lengths[idx] += distance;
+            (self.ants[idx * 2], self.ants[idx * 2 + 1]) = next_pos;
+            self.ant_index.update(idx, next_pos);
+            self.dirs[idx] = angle::wrap(next_dir);
+            let lost = self.config.boundary == Boundary::Open
+                && CellCoord::from_pos(next_pos, columns, rows).is_none();
+            if self.config.lifecycle {
+                self.energy[idx] -= distance * self.config.movement_cost;
+            }
+            if lost || (self.config.lifecycle && self.energy[idx] <= 0.0) {
+                removed.push(idx);
+            }
+        }
+
+        // Highest first, so every ant swapped into a freed slot is alive.
+        for idx in removed.into_iter().rev() {
+            self.colonies[self.ant_colonies[idx]].stats.deaths += 1;
+            self.remove_ant(idx);
+        }
+
+        if self.pheromone_timer == 0 {
+            self.pheromone_timer = self.config.pheromone_timer;
+        }
+    }
+
+    /// Heading an ant carrying food at `pos` takes towards its nest. Flow field
+    /// homing falls back to a straight line where the field has no answer, and
+    /// path integration gives none once the ant believes it is home.
+    fn homing_dir(&self, idx: usize, colony: usize, pos: (f32, f32)) -> Option<f32> {
+        let direct = || dir_to_nest(pos, self.colonies[colony].nest_pos);
+        match (&self.flow_field, self.config.homing) {
+            (Some(field), Homing::FlowField) => {
+                Some(field.direction(colony, pos).unwrap_or_else(direct))
+            }
+            (_, Homing::PathIntegration) => {
+                let (x, y) = (self.home_vectors[idx * 2], self.home_vectors[idx * 2 + 1]);
+                (x * x + y * y >= (GRID_SIZE / 2.0).powi(2)).then(|| (-y).atan2(-x))
+            }
+            _ => Some(direct()),
+        }
+    }
+
+    /// Adds one step of movement to an ant's home vector, turned by the
+    /// configured drift and a random heading error.
+    fn integrate_path(&mut self, idx: usize, step: (f32, f32)) {
+        let error =
+            self.config.odometry_drift + (self.rng.gen::<f32>() - 0.5) * self.config.odometry_noise;
+        let (sin, cos) = error.sin_cos();
+        self.home_vectors[idx * 2] += step.0 * cos - step.1 * sin;
+        self.home_vectors[idx * 2 + 1] += step.0 * sin + step.1 * cos;
+    }
+
+    fn pick_up_food(&mut self, idx: usize, cell: CellCoord) {
+        self.has_food[idx] = true;
+        self.take_food(cell);
+    }
+
+    /// Delivers any food the ant carries and starts its next trip.
+    fn reach_nest(&mut self, idx: usize, colony: usize) {
+        if self.has_food[idx] {
+            self.colonies[colony]
+                .stats
+                .record_trip(self.trip_steps[idx], self.trip_lengths[idx]);
+        }
+        if self.config.lifecycle {
+            self.refill(idx, colony);
+        }
+        self.has_food[idx] = false;
+        self.trip_steps[idx] = 0;
+        self.trip_lengths[idx] = 0.0;
+        self.home_vectors[idx * 2..idx * 2 + 2].fill(0.0);
+    }
+
+    /// Tops up an ant's energy from its colony's food store.
+    fn refill(&mut self, idx: usize, colony: usize) {
+        if self.config.energy_per_food <= 0.0 {
+            return;
+        }
+        let stats = &mut self.colonies[colony].stats;
+        let wanted = (self.config.max_energy - self.energy[idx]) / self.config.energy_per_food;
+        let eaten = wanted.min(stats.food_stored).max(0.0);
+        stats.food_stored -= eaten;
+        self.energy[idx] += eaten * self.config.energy_per_food;
+    }
+
+    /// Spawns one ant at each nest whose colony has stored enough food.
+    fn spawn_ants(&mut self) {
+        for colony in 0..self.colonies.len() {
+            let stats = &mut self.colonies[colony].stats;
+            if stats.food_stored <= self.config.spawn_threshold
+                || stats.food_stored < self.config.spawn_cost
+            {
+                continue;
+            }
+            stats.food_stored -= self.config.spawn_cost;
+            stats.births += 1;
+            self.spawn_ant(colony);
+        }
+    }
+
+    fn spawn_ant(&mut self, colony: usize) {
+        let nest_pos = self.colonies[colony].nest_pos;
+        self.ant_index.insert(self.dirs.len(), nest_pos);
+        self.ants.extend([nest_pos.0, nest_pos.1]);
+        self.dirs.push(self.rng.gen::<f32>() * 2.0 * PI);
+        self.has_food.push(false);
+        self.ant_colonies.push(colony);
+        self.trip_steps.push(0);
+        self.trip_lengths.push(0.0);
+        self.energy.push(self.config.max_energy);
+        self.home_vectors.extend([0.0, 0.0]);
+        self.castes.push(Caste::pick(&self.config, &mut self.rng));
+    }
+
+    /// Removes an ant by moving the last ant into its slot.
+    fn remove_ant(&mut self, idx: usize) {
+        let last = self.dirs.len() - 1;
+        self.ants.swap(idx * 2, last * 2);
+        self.ants.swap(idx * 2 + 1, last * 2 + 1);
+        self.ants.truncate(last * 2);
+        self.dirs.swap_remove(idx);
+        self.has_food.swap_remove(idx);
+        self.ant_colonies.swap_remove(idx);
+        self.trip_steps.swap_remove(idx);
+        self.trip_lengths.swap_remove(idx);
+        self.energy.swap_remove(idx);
+        self.home_vectors.swap(idx * 2, last * 2);
+        self.home_vectors.swap(idx * 2 + 1, last * 2 + 1);
+        self.home_vectors.truncate(last * 2);
+        self.castes.swap_remove(idx);
+        self.ant_index.swap_remove(idx);
+    }
+
+    /// Buckets are at least a grid cell wide so a zero view radius can't
+    /// create an unbounded number of them.
+    fn ant_index(width: f32, height: f32, config: &SimConfig, ants: &[f32]) -> SpatialHash {
+        SpatialHash::from_positions(width, height, config.view_radius.max(GRID_SIZE), ants)
+    }
+
+    fn rebuild_ant_index(&mut self) {
+        self.ant_index = Simulation::ant_index(self.width, self.height, &self.config, &self.ants);
+    }
+
+    fn take_food(&mut self, cell: CellCoord) {
+        let (columns, _) = grid_dimensions(self.width, self.height);
+        let idx = cell.idx(columns);
+        self.grid[idx + 1] -= 1.0;
+        if self.grid[idx + 1] <= 0.0 {
+            self.grid[idx] = 0.0;
+            self.grid[idx + 1] = 0.0;
+        }
+        self.grid_version += 1;
+    }
+
+    /// Moves `speed` along `dir` from `pos`, reflecting off walls and other
+    /// colonies' nests. Stops just short of food or the ant's own nest and
+    /// returns what it touched. The position returned always lies in a cell
+    /// `passable` accepts or in the cell the ant started in, so an ant caught
+    /// inside something it can't pass is free to walk out of it.
+    fn walk(
+        &self,
+        pos: (f32, f32),
+        dir: f32,
+        speed: f32,
+        colony: usize,
+        passable: impl Fn(GridResource) -> bool,
+    ) -> ((f32, f32), f32, Option<Contact>) {
+        let start = CellCoord::unbounded(pos);
+        let allowed = |pos: (f32, f32)| {
+            let cell = CellCoord::unbounded(pos);
+            cell == start || passable(self.cell_resource(cell.0, cell.1))
+        };
+        let (mut pos, mut dir, mut remaining) = (pos, dir, speed);
+        for _ in 0..MAX_BOUNCES {
+            let target = next_ant_position(pos, dir, remaining);
+            let hit = sweep(pos, target, GRID_SIZE, |cell| {
+                !passable(self.cell_resource(cell.0, cell.1))
+            });
+            let Some(hit) = hit else {
+                if allowed(target) {
+                    pos = target;
+                }
+                return (pos, dir, None);
+            };
+            if !allowed(hit.pos) {
+                return (pos, dir, None);
+            }
+            pos = hit.pos;
+            let resource = self.cell_resource(hit.cell.0, hit.cell.1);
+            let touched = match resource {
+                GridResource::Food => true,
+                GridResource::Nest(nest) => nest == colony,
+                _ => false,
+            };
+            match self.cell(hit.cell.0, hit.cell.1) {
+                Some(cell) if touched => return (pos, dir, Some((resource, cell))),
+                _ => {
+                    dir = reflect(dir, hit.normal);
+                    remaining *= 1.0 - hit.t;
+                }
+            }
+        }
+        (pos, dir, None)
+    }
+
+    /// Samples left, center and right sensors at the edges and middle of the
+    /// view arc and returns how far to turn towards the strongest one.
+    fn sensor_turn(
+        &self,
+        colony: usize,
+        channel: PheromoneChannel,
+        pos: (f32, f32),
+        dir: f32,
+    ) -> f32 {
+        let half_arc = self.config.view_arc / 2.0;
+        let radius = self.config.view_radius;
+        // Rotate the heading vector instead of calling sin/cos per sensor.
+        let (sin, cos) = dir.sin_cos();
+        let (arc_sin, arc_cos) = half_arc.sin_cos();
+        let sense = |d_x: f32, d_y: f32| {
+            self.pheromones.sample(
+                colony,
+                channel,
+                (pos.0 + d_x * radius, pos.1 + d_y * radius),
+            )
+        };
+        let left = sense(cos * arc_cos + sin * arc_sin, sin * arc_cos - cos * arc_sin);
+        let center = sense(cos, sin);
+        let right = sense(cos * arc_cos - sin * arc_sin, sin * arc_cos + cos * arc_sin);
+
+        if center >= left && center >= right {
+            0.0
+        } else if left > right {
+            -self.config.steering_strength.min(half_arc)
+        } else if right > left {
+            self.config.steering_strength.min(half_arc)
+        } else {
+            0.0
+        }
+    }
+
+    fn decay_pheromones(&mut self) {
+        for colony in 0..self.pheromones.colonies() {
+            for channel in PheromoneChannel::ALL {
+                let decay = match channel {
+                    PheromoneChannel::Food => self.config.food_pheromone_decay,
+                    PheromoneChannel::Home => self.config.home_pheromone_decay,
+                };
+                self.pheromones
+                    .evaporate(colony, channel, self.config.evaporation, decay);
+                self.pheromones.diffuse(
+                    colony,
+                    channel,
+                    self.config.diffusion,
+                    self.config.diffusion_rate,
+                );
+            }
+        }
+    }
+
+    /// Indices of ants within `radius` of `pos` and `arc / 2` of `dir`.
+    pub fn ants_within(&self, pos: (f32, f32), radius: f32, dir: f32, arc: f32) -> Vec<usize> {
+        self.ant_index.query_arc(pos, radius, dir, arc)
+    }
+
+    pub fn pheromones_within(
+        &self,
+        colony: usize,
+        channel: PheromoneChannel,
+        pos: (f32, f32),
+        radius: f32,
+        dir: f32,
+        arc: f32,
+    ) -> Vec<((f32, f32), f32)> {
+        self.pheromones
+            .cells_within(colony, channel, pos, radius, dir, arc)
+    }
+
+    /// The resource at `pos`, with the world boundary applied outside the grid.
+    pub fn resource_at(&self, pos: (f32, f32)) -> GridResource {
+        let (column, row) = CellCoord::unbounded(pos);
+        self.cell_resource(column, row)
+    }
+
+    fn cell_resource(&self, column: isize, row: isize) -> GridResource {
+        let (columns, _) = grid_dimensions(self.width, self.height);
+        match self.cell(column, row) {
+            Some(cell) => get_resource_in_cell(&self.grid, columns, cell),
+            None if self.config.boundary == Boundary::Open => GridResource::Blank,
+            None => GridResource::Wall,
+        }
+    }
+
+    /// The grid cell at `column, row`, wrapped around when the world wraps.
+    fn cell(&self, column: isize, row: isize) -> Option<CellCoord> {
+        let (columns, rows) = grid_dimensions(self.width, self.height);
+        match self.config.boundary {
+            Boundary::Wrap => Some(CellCoord::wrapped(column, row, columns, rows)),
+            Boundary::Solid | Boundary::Open => CellCoord::new(column, row, columns, rows),
+        }
+    }
+
+    fn wrap_position(&self, pos: (f32, f32)) -> (f32, f32) {
+        let (columns, rows) = grid_dimensions(self.width, self.height);
+        let wrap = |value: f32, extent: f32| {
+            let wrapped = value.rem_euclid(extent);
+            if wrapped >= extent {
+                0.0
+            } else {
+                wrapped
+            }
+        };
+        (
+            wrap(pos.0, columns as f32 * GRID_SIZE),
+            wrap(pos.1, rows as f32 * GRID_SIZE),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::caste::CasteParams;
+
+    fn seeded(seed: u64) -> Simulation {
+        let config = SimConfig {
+            ant_count: 200,
+            seed,
+            ..SimConfig::default()
+        };
+        Simulation::new(600.0, 400.0, config).unwrap()
+    }
+
+    fn trajectory_bits(simulation: &Simulation) -> Vec<u32> {
+        simulation
+            .ants
+            .iter()
+            .chain(&simulation.dirs)
+            .map(|value| value.to_bits())
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_produces_identical_trajectories() {
+        let mut a = seeded(42);
+        let mut b = seeded(42);
+        assert_eq!(trajectory_bits(&a), trajectory_bits(&b));
+        for _ in 0..500 {
+            a.step();
+            b.step();
+            assert_eq!(trajectory_bits(&a), trajectory_bits(&b));
+        }
+        assert_eq!(a.has_food, b.has_food);
+        assert_eq!(a.stats(), b.stats());
+    }
+
+    #[test]
+    fn different_seeds_diverge() {
+        let mut a = seeded(1);
+        let mut b = seeded(2);
+        for _ in 0..10 {
+            a.step();
+            b.step();
+        }
+        assert_ne!(trajectory_bits(&a), trajectory_bits(&b));
+    }
+
+    #[test]
+    fn load_map_restarts_from_seed() {
+        let map = Map::parse_ascii("..F\n.N.\n").unwrap();
+        let mut a = seeded(7);
+        for _ in 0..100 {
+            a.step();
+        }
+        a.load_map(&map).unwrap();
+        let mut b = seeded(7);
+        b.load_map(&map).unwrap();
+        for _ in 0..200 {
+            a.step();
+            b.step();
+        }
+        assert_eq!(trajectory_bits(&a), trajectory_bits(&b));
+    }
+
+    fn assert_outside_walls(simulation: &Simulation) {
+        for (idx, pos) in simulation.ants.chunks_exact(2).enumerate() {
+            let resource = simulation.resource_at((pos[0], pos[1]));
+            assert!(
+                resource != GridResource::Wall,
+                "Ant {} is inside a wall at {:?} after {} steps",
+                idx,
+                pos,
+                simulation.steps
+            );
+            if let GridResource::Nest(nest) = resource {
+                assert_eq!(nest, simulation.ant_colonies[idx]);
+            }
+        }
+    }
+
+    #[test]
+    fn ants_never_enter_walls() {
+        let map = Map::parse_ascii(concat!(
+            "#..#......#.........\n",
+            ".#..#....#..##..F...\n",
+            "..#..#..#...#.......\n",
+            "...#..##....#..#.#..\n",
+            "....N.......####.#..\n",
+            "...#..##.........#..\n",
+            "..#..#..#....1..###.\n",
+            ".#..#....#..........\n",
+            "#..#......#...#.#.#.\n",
+            ".F..........#.#.#.#.\n",
+        ))
+        .unwrap();
+        let config = SimConfig {
+            ant_count: 200,
+            walk_speed: 6.0,
+            wander_coefficient: 2.0,
+            ..SimConfig::default()
+        };
+        let mut simulation = Simulation::new(200.0, 100.0, config).unwrap();
+        simulation.load_map(&map).unwrap();
+        for _ in 0..2000 {
+            simulation.step();
+            assert_outside_walls(&simulation);
+        }
+    }
+
+    #[test]
+    fn painted_walls_return_ants_to_their_nest() {
+        let mut simulation = seeded(3);
+        for _ in 0..50 {
+            simulation.step();
+        }
+        let (x, y) = (simulation.ants[0], simulation.ants[1]);
+        simulation.paint((x, y), BrushKind::Wall, 1);
+        assert_outside_walls(&simulation);
+        for _ in 0..200 {
+            simulation.step();
+            assert_outside_walls(&simulation);
+        }
+    }
+
+    #[test]
+    fn grid_matches_world_size() {
+        for (width, height) in [(600.0, 400.0), (605.0, 403.0)] {
+            let simulation = Simulation::new(width, height, SimConfig::default()).unwrap();
+            assert_eq!(simulation.grid.len(), 60 * 40 * 2);
+        }
+    }
+
+    #[test]
+    fn far_edges_follow_the_boundary() {
+        let mut simulation = seeded(0);
+        simulation.paint((5.0, 5.0), BrushKind::Food, 0);
+        for pos in [(600.0, 5.0), (5.0, 400.0), (600.0, 400.0), (-0.5, 5.0)] {
+            assert_eq!(simulation.resource_at(pos), GridResource::Wall);
+        }
+        simulation.config.boundary = Boundary::Open;
+        assert_eq!(simulation.resource_at((600.0, 5.0)), GridResource::Blank);
+        simulation.config.boundary = Boundary::Wrap;
+        assert_eq!(simulation.resource_at((600.0, 5.0)), GridResource::Food);
+        assert_eq!(simulation.resource_at((5.0, -395.0)), GridResource::Food);
+    }
+
+    #[test]
+    fn wrapping_keeps_ants_inside_the_world() {
+        let config = SimConfig {
+            ant_count: 200,
+            boundary: Boundary::Wrap,
+            wander_coefficient: 1.0,
+            ..SimConfig::default()
+        };
+        let mut simulation = Simulation::new(200.0, 100.0, config).unwrap();
+        for _ in 0..2000 {
+            simulation.step();
+            for pos in simulation.ants.chunks_exact(2) {
+                assert!((0.0..200.0).contains(&pos[0]) && (0.0..100.0).contains(&pos[1]));
+            }
+            assert_outside_walls(&simulation);
+        }
+        assert_eq!(simulation.dirs.len(), 200);
+    }
+
+    #[test]
+    fn open_edges_remove_ants() {
+        let config = SimConfig {
+            ant_count: 200,
+            boundary: Boundary::Open,
+            ..SimConfig::default()
+        };
+        let mut simulation = Simulation::new(200.0, 100.0, config).unwrap();
+        for _ in 0..2000 {
+            simulation.step();
+            for pos in simulation.ants.chunks_exact(2) {
+                assert!((0.0..200.0).contains(&pos[0]) && (0.0..100.0).contains(&pos[1]));
+            }
+        }
+        let lost = 200 - simulation.dirs.len();
+        assert!(lost > 0);
+        assert_eq!(simulation.stats().deaths as usize, lost);
+    }
+
+    fn lifecycle(seed: u64) -> Simulation {
+        let config = SimConfig {
+            lifecycle: true,
+            ..seeded(seed).config
+        };
+        Simulation::new(600.0, 400.0, config).unwrap()
+    }
+
+    fn assert_ant_arrays_agree(simulation: &Simulation) {
+        let count = simulation.dirs.len();
+        assert_eq!(simulation.ants.len(), count * 2);
+        assert_eq!(simulation.has_food.len(), count);
+        assert_eq!(simulation.ant_colonies.len(), count);
+        assert_eq!(simulation.trip_steps.len(), count);
+        assert_eq!(simulation.trip_lengths.len(), count);
+        assert_eq!(simulation.energy.len(), count);
+        assert_eq!(simulation.home_vectors.len(), count * 2);
+        assert_eq!(simulation.castes.len(), count);
+        let mut indexed = simulation.ants_within((0.0, 0.0), 1e6, 0.0, 2.0 * PI);
+        indexed.sort_unstable();
+        assert_eq!(indexed, (0..count).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn energy_drains_with_distance_walked() {
+        let mut simulation = lifecycle(3);
+        simulation.step();
+        let config = simulation.config;
+        for idx in 0..simulation.dirs.len() {
+            let expected = config.max_energy - simulation.trip_lengths[idx] * config.movement_cost;
+            assert!((simulation.energy[idx] - expected).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn ants_refill_from_the_food_store() {
+        let mut simulation = lifecycle(3);
+        let config = simulation.config;
+        simulation.energy[0] = config.max_energy - config.energy_per_food;
+        simulation.colonies[0].stats.food_stored = 3.0;
+        simulation.reach_nest(0, 0);
+        assert_eq!(simulation.energy[0], config.max_energy);
+        assert_eq!(simulation.colonies[0].stats.food_stored, 2.0);
+
+        simulation.energy[0] = config.max_energy - config.energy_per_food;
+        simulation.colonies[0].stats.food_stored = 0.5;
+        simulation.reach_nest(0, 0);
+        assert_eq!(
+            simulation.energy[0],
+            config.max_energy - config.energy_per_food / 2.0
+        );
+        assert_eq!(simulation.colonies[0].stats.food_stored, 0.0);
+    }
+
+    #[test]
+    fn starving_ants_die() {
+        let mut simulation = lifecycle(3);
+        simulation.energy.fill(1e-3);
+        simulation.energy[0] = simulation.config.max_energy;
+        simulation.step();
+        assert_eq!(simulation.dirs.len(), 1);
+        assert_eq!(simulation.stats().deaths, 199);
+        assert_ant_arrays_agree(&simulation);
+    }
+
+    #[test]
+    fn colonies_spawn_from_stored_food() {
+        let mut simulation = lifecycle(3);
+        let config = simulation.config;
+        simulation.colonies[0].stats.food_stored = config.spawn_threshold;
+        simulation.spawn_ants();
+        assert_eq!(simulation.dirs.len(), 200);
+
+        simulation.colonies[0].stats.food_stored = config.spawn_threshold + 1.0;
+        simulation.spawn_ants();
+        assert_eq!(simulation.dirs.len(), 201);
+        assert_eq!(
+            simulation.colonies[0].stats.food_stored,
+            config.spawn_threshold + 1.0 - config.spawn_cost
+        );
+        assert_eq!(simulation.colonies[0].stats.births, 1);
+        assert_eq!(
+            (simulation.ants[400], simulation.ants[401]),
+            simulation.colonies[0].nest_pos
+        );
+        assert_eq!(simulation.energy[200], config.max_energy);
+        assert_ant_arrays_agree(&simulation);
+    }
+
+    #[test]
+    fn removed_ants_are_replaced_by_the_last_ant() {
+        let mut simulation = lifecycle(3);
+        for _ in 0..100 {
+            simulation.step();
+        }
+        let last = simulation.dirs.len() - 1;
+        simulation.castes[last] = Caste::Soldier;
+        let moved = (
+            (simulation.ants[last * 2], simulation.ants[last * 2 + 1]),
+            simulation.dirs[last],
+            simulation.has_food[last],
+            simulation.ant_colonies[last],
+            simulation.trip_steps[last],
+            simulation.trip_lengths[last],
+            simulation.energy[last],
+            (
+                simulation.home_vectors[last * 2],
+                simulation.home_vectors[last * 2 + 1],
+            ),
+            simulation.castes[last],
+        );
+        simulation.remove_ant(5);
+        assert_eq!(
+            moved,
+            (
+                (simulation.ants[10], simulation.ants[11]),
+                simulation.dirs[5],
+                simulation.has_food[5],
+                simulation.ant_colonies[5],
+                simulation.trip_steps[5],
+                simulation.trip_lengths[5],
+                simulation.energy[5],
+                (simulation.home_vectors[10], simulation.home_vectors[11]),
+                simulation.castes[5],
+            )
+        );
+        assert_ant_arrays_agree(&simulation);
+        assert!(simulation
+            .ants_within(moved.0, 0.0, 0.0, 2.0 * PI)
+            .contains(&5));
+    }
+
+    #[test]
+    fn noiseless_odometry_tracks_the_nest() {
+        let config = SimConfig {
+            ant_count: 200,
+            homing: Homing::PathIntegration,
+            odometry_noise: 0.0,
+            ..SimConfig::default()
+        };
+        let mut simulation = Simulation::new(600.0, 400.0, config).unwrap();
+        let nest_pos = simulation.colonies[0].nest_pos;
+        for _ in 0..300 {
+            simulation.step();
+        }
+        let mut checked = 0;
+        for idx in 0..simulation.dirs.len() {
+            if u64::from(simulation.trip_steps[idx]) != simulation.steps {
+                continue;
+            }
+            let offset = (
+                simulation.ants[idx * 2] - nest_pos.0,
+                simulation.ants[idx * 2 + 1] - nest_pos.1,
+            );
+            let vector = (
+                simulation.home_vectors[idx * 2],
+                simulation.home_vectors[idx * 2 + 1],
+            );
+            assert!((offset.0 - vector.0).abs() < 0.1 && (offset.1 - vector.1).abs() < 0.1);
+            checked += 1;
+        }
+        assert!(checked > 0);
+    }
+
+    #[test]
+    fn path_integration_brings_food_home() {
+        let config = SimConfig {
+            ant_count: 500,
+            homing: Homing::PathIntegration,
+            ..SimConfig::default()
+        };
+        let mut simulation = Simulation::new(600.0, 400.0, config).unwrap();
+        for _ in 0..3000 {
+            simulation.step();
+        }
+        assert!(simulation.stats().trips > 0);
+    }
+
+    #[test]
+    fn food_ringed_nest_still_receives_deliveries() {
+        let map = Map::parse_ascii("...FFFF...\n...FNNF...\n...FNNF...\n...FFFF...\n").unwrap();
+        let config = SimConfig {
+            ant_count: 200,
+            ..SimConfig::default()
+        };
+        let mut simulation = Simulation::new(120.0, 80.0, config).unwrap();
+        simulation.load_map(&map).unwrap();
+        for _ in 0..2000 {
+            simulation.step();
+        }
+        assert!(simulation.stats().trips > 0);
+        assert!(simulation.has_food.iter().any(|&carrying| !carrying));
+    }
+
+    fn two_colonies(ascii: &str) -> Simulation {
+        let mut simulation = Simulation::new(200.0, 100.0, seeded(8).config).unwrap();
+        simulation
+            .load_map(&Map::parse_ascii(ascii).unwrap())
+            .unwrap();
+        simulation
+    }
+
+    #[test]
+    fn ants_only_sense_their_own_colony_trails() {
+        let mut simulation = two_colonies("N........1\n");
+        let pos = (100.0, 50.0);
+        let (radius, half_arc) = (
+            simulation.config.view_radius,
+            simulation.config.view_arc / 2.0,
+        );
+        let left = (
+            pos.0 + half_arc.cos() * radius,
+            pos.1 - half_arc.sin() * radius,
+        );
+        let right = (
+            pos.0 + half_arc.cos() * radius,
+            pos.1 + half_arc.sin() * radius,
+        );
+        let turn = simulation.config.steering_strength.min(half_arc);
+        simulation
+            .pheromones
+            .add_pheromone(1, PheromoneChannel::Food, left, 1.0);
+        assert_eq!(
+            simulation.sensor_turn(0, PheromoneChannel::Food, pos, 0.0),
+            0.0
+        );
+        assert_eq!(
+            simulation.sensor_turn(1, PheromoneChannel::Food, pos, 0.0),
+            -turn
+        );
+        simulation
+            .pheromones
+            .add_pheromone(0, PheromoneChannel::Food, right, 1.0);
+        assert_eq!(
+            simulation.sensor_turn(0, PheromoneChannel::Food, pos, 0.0),
+            turn
+        );
+        assert_eq!(
+            simulation.sensor_turn(1, PheromoneChannel::Food, pos, 0.0),
+            -turn
+        );
+    }
+
+    #[test]
+    fn colony_totals_stay_separate() {
+        let mut simulation = two_colonies(
+            "NN.......#..........\n\
+             NN.......#..........\n\
+             .........#..........\n\
+             FFF......#..........\n\
+             FFF......#........11\n\
+             .........#........11\n\
+             .........#..........\n\
+             .........#..........\n\
+             .........#..........\n\
+             .........#..........\n",
+        );
+        for _ in 0..3000 {
+            simulation.step();
+        }
+        let (first, second) = (
+            simulation.colony_stats(0).unwrap(),
+            simulation.colony_stats(1).unwrap(),
+        );
+        assert!(first.food_delivered > 0);
+        assert_eq!(second.food_delivered, 0);
+        assert_eq!(second.food_stored, 0.0);
+        assert_eq!(simulation.colonies[0].stats.food_stored, first.food_stored);
+        assert_eq!(first.ant_count + second.ant_count, 200);
+        let total = simulation.stats();
+        assert_eq!(total.food_delivered, first.food_delivered);
+        assert_eq!(total.ant_count, 200);
+    }
+
+    #[test]
+    fn picking_up_food_empties_the_cell() {
+        let config = SimConfig {
+            food_per_cell: 2.0,
+            ..seeded(0).config
+        };
+        let mut simulation = Simulation::new(600.0, 400.0, config).unwrap();
+        let pos = (25.0, 25.0);
+        simulation.paint(pos, BrushKind::Food, 0);
+        let cell = CellCoord::from_pos(pos, 60, 40).unwrap();
+        let idx = cell.idx(60);
+        let version = simulation.grid_version;
+
+        simulation.pick_up_food(0, cell);
+        assert!(simulation.has_food[0]);
+        assert_eq!(simulation.resource_at(pos), GridResource::Food);
+        assert_eq!(simulation.grid[idx + 1], 1.0);
+        assert!(simulation.grid_version > version);
+
+        simulation.pick_up_food(1, cell);
+        assert_eq!(simulation.resource_at(pos), GridResource::Blank);
+        assert_eq!(simulation.grid[idx..idx + 2], [0.0, 0.0]);
+    }
+
+    #[test]
+    fn caste_speed_scales_the_step() {
+        for speed in [0.5, 2.0] {
+            let config = SimConfig {
+                scout_ratio: 1.0,
+                forager_ratio: 0.0,
+                scout: CasteParams {
+                    speed,
+                    ..SimConfig::default().scout
+                },
+                ..seeded(2).config
+            };
+            let mut simulation = Simulation::new(600.0, 400.0, config).unwrap();
+            assert!(simulation.castes.iter().all(|&caste| caste == Caste::Scout));
+            simulation.step();
+            for &length in &simulation.trip_lengths {
+                assert!((length - config.walk_speed * speed).abs() < 1e-3);
+            }
+        }
+    }
+
+    #[test]
+    fn rejected_maps_leave_the_simulation_alone() {
+        let mut simulation = seeded(0);
+        simulation.step();
+        let before = simulation.snapshot().to_bytes();
+        let map = Map {
+            columns: 3,
+            rows: 1,
+            cells: vec![GridResource::Food; 3],
+        };
+        assert_eq!(simulation.load_map(&map), Err(MapError::MissingNest));
+        let map = Map {
+            columns: 2,
+            rows: 1,
+            cells: vec![GridResource::Nest(1), GridResource::Blank],
+        };
+        assert_eq!(simulation.load_map(&map), Err(MapError::MissingNest));
+        assert_eq!(simulation.snapshot().to_bytes(), before);
+    }
+
+    #[test]
+    fn ants_under_painted_food_pick_it_up() {
+        let mut simulation = seeded(5);
+        for _ in 0..100 {
+            simulation.step();
+        }
+        let pos = |simulation: &Simulation, idx: usize| {
+            (simulation.ants[idx * 2], simulation.ants[idx * 2 + 1])
+        };
+        for idx in 0..20 {
+            simulation.paint(pos(&simulation, idx), BrushKind::Food, 0);
+        }
+        let painted: Vec<usize> = (0..20)
+            .filter(|&idx| {
+                !simulation.has_food[idx]
+                    && simulation.resource_at(pos(&simulation, idx)) == GridResource::Food
+            })
+            .collect();
+        assert!(!painted.is_empty());
+        let before = simulation.ants.clone();
+        simulation.step();
+        for &idx in &painted {
+            assert!(simulation.has_food[idx], "Ant {} left the food behind", idx);
+        }
+        for _ in 0..100 {
+            simulation.step();
+        }
+        for &idx in &painted {
+            assert_ne!(
+                (before[idx * 2], before[idx * 2 + 1]),
+                (simulation.ants[idx * 2], simulation.ants[idx * 2 + 1]),
+                "Ant {} is frozen",
+                idx
+            );
+        }
+    }
+
+    #[test]
+    fn worlds_smaller_than_a_cell_are_rejected() {
+        for (width, height) in [(5.0, 5.0), (0.0, 400.0), (600.0, 9.9), (f32::NAN, 10.0)] {
+            assert!(Simulation::new(width, height, SimConfig::default()).is_err());
+            let mut simulation = seeded(0);
+            assert!(simulation
+                .apply(Intervention::Resize { width, height })
+                .is_err());
+            assert_eq!((simulation.width, simulation.height), (600.0, 400.0));
+            simulation.step();
+        }
+    }
+
+    #[test]
+    fn invalid_configs_are_rejected() {
+        let config = SimConfig {
+            pheromone_cell_size: 0.0,
+            ..SimConfig::default()
+        };
+        assert!(Simulation::new(600.0, 400.0, config).is_err());
+
+        let mut simulation = seeded(0);
+        let mut snapshot = simulation.snapshot();
+        snapshot.config = config;
+        assert!(simulation.restore(snapshot).is_err());
+        assert!(simulation
+            .apply(Intervention::SetConfig { config })
+            .is_err());
+        simulation.step();
+    }
+
+    #[test]
+    fn huge_brushes_paint_the_whole_grid() {
+        let mut simulation = seeded(0);
+        simulation.paint((5.0, 5.0), BrushKind::Food, u32::MAX);
+        let resources: Vec<_> = simulation
+            .grid
+            .chunks_exact(2)
+            .map(|cell| cell[0] as usize)
+            .collect();
+        assert!(resources
+            .iter()
+            .all(|&resource| resource == 1 || resource == 2));
+        assert!(resources.contains(&1));
+    }
+
+    #[test]
+    fn single_cell_worlds_run() {
+        for size in [10.0, 15.0] {
+            let mut simulation = Simulation::new(size, size, seeded(0).config).unwrap();
+            for _ in 0..50 {
+                simulation.step();
+            }
+            assert_outside_walls(&simulation);
+        }
+        let mut simulation = Simulation::new(10.0, 10.0, SimConfig::default()).unwrap();
+        simulation
+            .load_map(&Map::parse_ascii("N\n").unwrap())
+            .unwrap();
+        simulation.step();
+    }
+
+    fn assert_ants_within_matches_brute_force(simulation: &Simulation, radius: f32) {
+        let pos = simulation.colonies[0].nest_pos;
+        let mut found = simulation.ants_within(pos, radius, 0.0, 2.0 * PI);
+        found.sort_unstable();
+        let expected: Vec<usize> = (0..simulation.dirs.len())
+            .filter(|&idx| {
+                let d_x = simulation.ants[idx * 2] - pos.0;
+                let d_y = simulation.ants[idx * 2 + 1] - pos.1;
+                d_x * d_x + d_y * d_y <= radius * radius
+            })
+            .collect();
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn ants_within_sees_ants_after_they_move() {
+        let mut simulation = seeded(9);
+        for _ in 0..3 {
+            for _ in 0..50 {
+                simulation.step();
+            }
+            assert_ants_within_matches_brute_force(&simulation, 60.0);
+        }
+    }
+
+    #[test]
+    fn ants_within_follows_births_and_deaths() {
+        let config = SimConfig {
+            lifecycle: true,
+            boundary: Boundary::Open,
+            movement_cost: 5.0,
+            ..seeded(4).config
+        };
+        let mut simulation = Simulation::new(200.0, 150.0, config).unwrap();
+        simulation.colonies[0].stats.food_stored = 200.0;
+        for _ in 0..20 {
+            for _ in 0..10 {
+                simulation.step();
+            }
+            assert_ants_within_matches_brute_force(&simulation, 40.0);
+            assert_ants_within_matches_brute_force(&simulation, 1000.0);
+        }
+        let stats = &simulation.colonies[0].stats;
+        assert!(stats.births > 0 && stats.deaths > 0);
+    }
+
+    #[test]
+    fn replays_reproduce_recorded_interventions() {
+        let mut original = seeded(21);
+        original.paint((150.0, 150.0), BrushKind::Wall, 3);
+        original.paint((450.0, 250.0), BrushKind::Food, 2);
+        original.start_recording().unwrap();
+        let interventions = [
+            Intervention::Paint {
+                pos: (300.0, 100.0),
+                brush: BrushKind::Food,
+                radius: 2,
+            },
+            Intervention::SetConfig {
+                config: SimConfig {
+                    wander_coefficient: original.config.wander_coefficient * 2.0,
+                    ..original.config
+                },
+            },
+            Intervention::LoadMap {
+                map: String::from("....F\n.N...\n....#\n"),
+            },
+            Intervention::Paint {
+                pos: (200.0, 200.0),
+                brush: BrushKind::Wall,
+                radius: 1,
+            },
+        ];
+        for intervention in interventions {
+            for _ in 0..40 {
+                original.step();
+            }
+            original.apply(intervention).unwrap();
+        }
+        for _ in 0..40 {
+            original.step();
+        }
+        let log = original.stop_recording().unwrap();
+        let log = ReplayLog::from_json(&log.to_json()).unwrap();
+
+        let mut replayed = seeded(5);
+        replayed.start_replay(log).unwrap();
+        assert!(replayed
+            .apply(Intervention::Paint {
+                pos: (300.0, 300.0),
+                brush: BrushKind::Wall,
+                radius: 1,
+            })
+            .is_err());
+        while replayed.steps < original.steps {
+            replayed.step();
+        }
+        assert!(!replayed.is_replaying());
+        assert_eq!(
+            replayed.snapshot().to_bytes(),
+            original.snapshot().to_bytes()
+        );
+    }
+}
@@ -0,0 +1,122 @@
+use crate::caste::Caste;
+use crate::colony::Colony;
+use crate::config::SimConfig;
+use crate::pheromones::PheromoneField;
+use rand_xoshiro::Xoshiro256Plus;
+use serde::{Deserialize, Serialize};
+
+/// Bumped whenever the snapshot layout changes so stale files are rejected.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Everything needed to resume a run exactly where it was saved, including the
+/// random stream.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    pub config: SimConfig,
+    pub width: f32,
+    pub height: f32,
+    pub ants: Vec<f32>,
+    pub dirs: Vec<f32>,
+    pub has_food: Vec<bool>,
+    pub ant_colonies: Vec<usize>,
+    pub trip_steps: Vec<u32>,
+    pub trip_lengths: Vec<f32>,
+    pub energy: Vec<f32>,
+    pub home_vectors: Vec<f32>,
+    pub castes: Vec<Caste>,
+    pub grid: Vec<f32>,
+    pub colonies: Vec<Colony>,
+    pub pheromones: PheromoneField,
+    pub steps: u64,
+    pub pheromone_timer: usize,
+    pub rng: Xoshiro256Plus,
+}
+
+impl Snapshot {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        bincode::serialize(self).expect("Error serializing snapshot")
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Snapshot, String> {
+        bincode::deserialize::<Snapshot>(bytes)
+            .map_err(|e| format!("Invalid snapshot: {}", e))?
+            .checked_version()
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("Error serializing snapshot")
+    }
+
+    pub fn from_json(json: &str) -> Result<Snapshot, String> {
+        serde_json::from_str::<Snapshot>(json)
+            .map_err(|e| format!("Invalid snapshot JSON: {}", e))?
+            .checked_version()
+    }
+
+    fn checked_version(self) -> Result<Snapshot, String> {
+        if self.version != SNAPSHOT_VERSION {
+            return Err(format!(
+                "Snapshot version {} is not supported, expected {}",
+                self.version, SNAPSHOT_VERSION
+            ));
+        }
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::Simulation;
+
+    fn running() -> Simulation {
+        let config = SimConfig {
+            ant_count: 150,
+            seed: 9,
+            ..SimConfig::default()
+        };
+        let mut simulation = Simulation::new(400.0, 300.0, config).unwrap();
+        for _ in 0..200 {
+            simulation.step();
+        }
+        simulation
+    }
+
+    fn assert_resumes_identically(decode: impl Fn(&Snapshot) -> Result<Snapshot, String>) {
+        let mut original = running();
+        let snapshot = decode(&original.snapshot()).unwrap();
+        let mut restored = Simulation::new(100.0, 100.0, SimConfig::default()).unwrap();
+        restored.restore(snapshot).unwrap();
+        assert_eq!(
+            restored.snapshot().to_bytes(),
+            original.snapshot().to_bytes()
+        );
+        for _ in 0..300 {
+            original.step();
+            restored.step();
+        }
+        assert_eq!(
+            restored.snapshot().to_bytes(),
+            original.snapshot().to_bytes()
+        );
+    }
+
+    #[test]
+    fn bincode_snapshots_resume_identically() {
+        assert_resumes_identically(|snapshot| Snapshot::from_bytes(&snapshot.to_bytes()));
+    }
+
+    #[test]
+    fn json_snapshots_resume_identically() {
+        assert_resumes_identically(|snapshot| Snapshot::from_json(&snapshot.to_json()));
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        let mut snapshot = running().snapshot();
+        snapshot.version = SNAPSHOT_VERSION + 1;
+        assert!(Snapshot::from_bytes(&snapshot.to_bytes()).is_err());
+        assert!(Snapshot::from_json(&snapshot.to_json()).is_err());
+    }
+}
@@ -0,0 +1,256 @@
+use crate::{angle, consts::PI};
+
+const NO_BUCKET: usize = usize::MAX;
+
+/// Uniform grid of buckets holding entry ids, updated incrementally as entries
+/// move so that neighbour queries only visit buckets overlapping the radius.
+pub struct SpatialHash {
+    bucket_size: f32,
+    columns: usize,
+    rows: usize,
+    buckets: Vec<Vec<usize>>,
+    entry_buckets: Vec<usize>,
+    entry_slots: Vec<usize>,
+    positions: Vec<(f32, f32)>,
+}
+
+impl SpatialHash {
+    pub fn new(width: f32, height: f32, bucket_size: f32) -> Self {
+        let columns = (width / bucket_size).ceil().max(1.0) as usize;
+        let rows = (height / bucket_size).ceil().max(1.0) as usize;
+        SpatialHash {
+            bucket_size,
+            columns,
+            rows,
+            buckets: vec![Vec::new(); columns * rows],
+            entry_buckets: Vec::new(),
+            entry_slots: Vec::new(),
+            positions: Vec::new(),
+        }
+    }
+
+    pub fn from_positions(width: f32, height: f32, bucket_size: f32, positions: &[f32]) -> Self {
+        let mut hash = SpatialHash::new(width, height, bucket_size);
+        for (id, pos) in positions.chunks_exact(2).enumerate() {
+            hash.insert(id, (pos[0], pos[1]));
+        }
+        hash
+    }
+
+    fn bucket_coords(&self, pos: (f32, f32)) -> (usize, usize) {
+        let column = (pos.0 / self.bucket_size).max(0.0) as usize;
+        let row = (pos.1 / self.bucket_size).max(0.0) as usize;
+        (column.min(self.columns - 1), row.min(self.rows - 1))
+    }
+
+    fn bucket_index(&self, pos: (f32, f32)) -> usize {
+        let (column, row) = self.bucket_coords(pos);
+        row * self.columns + column
+    }
+
+    fn push_to_bucket(&mut self, id: usize, bucket: usize) {
+        self.entry_slots[id] = self.buckets[bucket].len();
+        self.buckets[bucket].push(id);
+        self.entry_buckets[id] = bucket;
+    }
+
+    fn take_from_bucket(&mut self, id: usize) {
+        let bucket = self.entry_buckets[id];
+        let slot = self.entry_slots[id];
+        let entries = &mut self.buckets[bucket];
+        entries.swap_remove(slot);
+        if let Some(&moved) = entries.get(slot) {
+            self.entry_slots[moved] = slot;
+        }
+        self.entry_buckets[id] = NO_BUCKET;
+    }
+
+    pub fn insert(&mut self, id: usize, pos: (f32, f32)) {
+        if id >= self.entry_buckets.len() {
+            self.entry_buckets.resize(id + 1, NO_BUCKET);
+            self.entry_slots.resize(id + 1, 0);
+            self.positions.resize(id + 1, (0.0, 0.0));
+        }
+        if self.entry_buckets[id] != NO_BUCKET {
+            self.update(id, pos);
+            return;
+        }
+        self.push_to_bucket(id, self.bucket_index(pos));
+        self.positions[id] = pos;
+    }
+
+    pub fn remove(&mut self, id: usize) {
+        if self
+            .entry_buckets
+            .get(id)
+            .is_some_and(|&bucket| bucket != NO_BUCKET)
+        {
+            self.take_from_bucket(id);
+        }
+    }
+
+    /// Removes `id` and moves the highest id into its place, mirroring
+    /// `Vec::swap_remove` on the arrays the ids index into.
+    pub fn swap_remove(&mut self, id: usize) {
+        let last = self.entry_buckets.len() - 1;
+        self.remove(id);
+        if id != last && self.entry_buckets[last] != NO_BUCKET {
+            let pos = self.positions[last];
+            self.take_from_bucket(last);
+            self.insert(id, pos);
+        }
+        self.entry_buckets.pop();
+        self.entry_slots.pop();
+        self.positions.pop();
+    }
+
+    /// Records a new position for `id`, only touching the buckets when the
+    /// entry crosses a bucket boundary.
+    pub fn update(&mut self, id: usize, pos: (f32, f32)) {
+        let bucket = self.bucket_index(pos);
+        let previous = self.entry_buckets[id];
+        if bucket != previous {
+            if previous != NO_BUCKET {
+                self.take_from_bucket(id);
+            }
+            self.push_to_bucket(id, bucket);
+        }
+        self.positions[id] = pos;
+    }
+
+    pub fn query_radius(&self, pos: (f32, f32), radius: f32) -> Vec<usize> {
+        self.query_arc(pos, radius, 0.0, 2.0 * PI)
+    }
+
+    /// Returns the ids within `radius` of `pos` whose bearing lies within
+    /// `arc / 2` of `dir`. An arc of `2π` or more matches every direction.
+    pub fn query_arc(&self, pos: (f32, f32), radius: f32, dir: f32, arc: f32) -> Vec<usize> {
+        let mut result = Vec::new();
+        let (min_column, min_row) = self.bucket_coords((pos.0 - radius, pos.1 - radius));
+        let (max_column, max_row) = self.bucket_coords((pos.0 + radius, pos.1 + radius));
+        for row in min_row..=max_row {
+            for column in min_column..=max_column {
+                for &id in &self.buckets[row * self.columns + column] {
+                    let entry_pos = self.positions[id];
+                    if within_arc(pos, radius, dir, arc, entry_pos) {
+                        result.push(id);
+                    }
+                }
+            }
+        }
+        result
+    }
+}
+
+pub fn within_arc(pos: (f32, f32), radius: f32, dir: f32, arc: f32, target: (f32, f32)) -> bool {
+    let d_x = target.0 - pos.0;
+    let d_y = target.1 - pos.1;
+    if d_x * d_x + d_y * d_y > radius * radius {
+        return false;
+    }
+    if arc >= 2.0 * PI || (d_x == 0.0 && d_y == 0.0) {
+        return true;
+    }
+    let bearing = d_y.atan2(d_x);
+    angle::shortest_diff(dir, bearing).abs() <= arc / 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::prelude::*;
+    use rand_xoshiro::rand_core::SeedableRng;
+    use rand_xoshiro::Xoshiro256Plus;
+
+    fn random_positions(rng: &mut impl Rng, count: usize) -> Vec<(f32, f32)> {
+        (0..count)
+            .map(|_| (rng.gen::<f32>() * 200.0, rng.gen::<f32>() * 100.0))
+            .collect()
+    }
+
+    fn brute_force(
+        positions: &[(f32, f32)],
+        pos: (f32, f32),
+        radius: f32,
+        dir: f32,
+        arc: f32,
+    ) -> Vec<usize> {
+        (0..positions.len())
+            .filter(|&id| within_arc(pos, radius, dir, arc, positions[id]))
+            .collect()
+    }
+
+    fn assert_matches_brute_force(
+        hash: &SpatialHash,
+        positions: &[(f32, f32)],
+        rng: &mut impl Rng,
+    ) {
+        for _ in 0..50 {
+            let pos = (rng.gen::<f32>() * 200.0, rng.gen::<f32>() * 100.0);
+            let radius = rng.gen::<f32>() * 40.0;
+            let dir = (rng.gen::<f32>() - 0.5) * 2.0 * PI;
+            let arc = rng.gen::<f32>() * 2.5 * PI;
+            let mut found = hash.query_arc(pos, radius, dir, arc);
+            found.sort_unstable();
+            assert_eq!(found, brute_force(positions, pos, radius, dir, arc));
+        }
+    }
+
+    #[test]
+    fn inserted_entries_match_brute_force() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(1);
+        let positions = random_positions(&mut rng, 500);
+        let flat: Vec<f32> = positions.iter().flat_map(|&(x, y)| [x, y]).collect();
+        let hash = SpatialHash::from_positions(200.0, 100.0, 10.0, &flat);
+        assert_matches_brute_force(&hash, &positions, &mut rng);
+    }
+
+    #[test]
+    fn updates_move_entries_between_buckets() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(2);
+        let mut positions = random_positions(&mut rng, 300);
+        let mut hash = SpatialHash::new(200.0, 100.0, 10.0);
+        for (id, &pos) in positions.iter().enumerate() {
+            hash.insert(id, pos);
+        }
+        for _ in 0..5 {
+            positions = random_positions(&mut rng, positions.len());
+            for (id, &pos) in positions.iter().enumerate() {
+                hash.update(id, pos);
+            }
+            assert_matches_brute_force(&hash, &positions, &mut rng);
+        }
+        let total: usize = hash.buckets.iter().map(Vec::len).sum();
+        assert_eq!(total, positions.len());
+    }
+
+    #[test]
+    fn swap_remove_mirrors_vec() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(3);
+        let mut positions = random_positions(&mut rng, 200);
+        let mut hash = SpatialHash::new(200.0, 100.0, 10.0);
+        for (id, &pos) in positions.iter().enumerate() {
+            hash.insert(id, pos);
+        }
+        while !positions.is_empty() {
+            let id = rng.gen_range(0..positions.len());
+            positions.swap_remove(id);
+            hash.swap_remove(id);
+            if positions.len() % 20 == 0 {
+                assert_matches_brute_force(&hash, &positions, &mut rng);
+            }
+        }
+        assert!(hash.buckets.iter().all(Vec::is_empty));
+        assert!(hash.query_radius((100.0, 50.0), 500.0).is_empty());
+    }
+
+    #[test]
+    fn removed_entries_are_not_found() {
+        let mut hash = SpatialHash::new(100.0, 100.0, 10.0);
+        hash.insert(0, (5.0, 5.0));
+        hash.insert(1, (6.0, 6.0));
+        hash.remove(0);
+        hash.remove(0);
+        assert_eq!(hash.query_radius((5.0, 5.0), 5.0), vec![1]);
+    }
+}
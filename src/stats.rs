@@ -0,0 +1,150 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+/// Foraging counters for one colony, or summed over all of them. A trip runs
+/// from an ant's last visit to its nest until it returns carrying food.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ColonyStats {
+    pub step: f64,
+    pub food_stored: f32,
+    pub food_delivered: u32,
+    pub trips: u32,
+    pub total_trip_steps: f64,
+    pub total_trip_length: f64,
+    pub ant_count: u32,
+    pub births: u32,
+    pub deaths: u32,
+}
+
+#[wasm_bindgen]
+impl ColonyStats {
+    #[wasm_bindgen(getter)]
+    pub fn average_trip_steps(&self) -> f64 {
+        if self.trips == 0 {
+            return 0.0;
+        }
+        self.total_trip_steps / self.trips as f64
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn average_trip_length(&self) -> f64 {
+        if self.trips == 0 {
+            return 0.0;
+        }
+        self.total_trip_length / self.trips as f64
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn trips_per_ant(&self) -> f64 {
+        self.per_ant(self.trips as f64)
+    }
+
+    /// Steps spent on completed trips, per living ant.
+    #[wasm_bindgen(getter)]
+    pub fn trip_steps_per_ant(&self) -> f64 {
+        self.per_ant(self.total_trip_steps)
+    }
+
+    /// Distance walked on completed trips, per living ant.
+    #[wasm_bindgen(getter)]
+    pub fn trip_length_per_ant(&self) -> f64 {
+        self.per_ant(self.total_trip_length)
+    }
+}
+
+impl ColonyStats {
+    fn per_ant(&self, total: f64) -> f64 {
+        if self.ant_count == 0 {
+            return 0.0;
+        }
+        total / self.ant_count as f64
+    }
+
+    pub fn record_trip(&mut self, steps: u32, length: f32) {
+        self.food_stored += 1.0;
+        self.food_delivered += 1;
+        self.trips += 1;
+        self.total_trip_steps += steps as f64;
+        self.total_trip_length += length as f64;
+    }
+
+    /// Adds `other`'s counters to these, keeping this step.
+    pub fn accumulate(&mut self, other: &ColonyStats) {
+        self.food_stored += other.food_stored;
+        self.food_delivered += other.food_delivered;
+        self.trips += other.trips;
+        self.total_trip_steps += other.total_trip_steps;
+        self.total_trip_length += other.total_trip_length;
+        self.ant_count += other.ant_count;
+        self.births += other.births;
+        self.deaths += other.deaths;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trips_feed_the_averages() {
+        let mut stats = ColonyStats {
+            ant_count: 4,
+            ..ColonyStats::default()
+        };
+        assert_eq!(stats.average_trip_steps(), 0.0);
+        assert_eq!(stats.average_trip_length(), 0.0);
+        stats.record_trip(10, 30.0);
+        stats.record_trip(30, 50.0);
+        assert_eq!(stats.food_stored, 2.0);
+        assert_eq!((stats.food_delivered, stats.trips), (2, 2));
+        assert_eq!(stats.average_trip_steps(), 20.0);
+        assert_eq!(stats.average_trip_length(), 40.0);
+        assert_eq!(stats.trips_per_ant(), 0.5);
+        assert_eq!(stats.trip_steps_per_ant(), 10.0);
+        assert_eq!(stats.trip_length_per_ant(), 20.0);
+    }
+
+    #[test]
+    fn per_ant_figures_are_zero_without_ants() {
+        let mut stats = ColonyStats::default();
+        stats.record_trip(10, 30.0);
+        assert_eq!(stats.trips_per_ant(), 0.0);
+        assert_eq!(stats.trip_steps_per_ant(), 0.0);
+        assert_eq!(stats.trip_length_per_ant(), 0.0);
+    }
+
+    #[test]
+    fn accumulate_sums_counters_and_keeps_the_step() {
+        let mut total = ColonyStats {
+            step: 7.0,
+            ant_count: 1,
+            births: 2,
+            ..ColonyStats::default()
+        };
+        total.record_trip(4, 8.0);
+        let mut other = ColonyStats {
+            step: 99.0,
+            ant_count: 3,
+            deaths: 1,
+            ..ColonyStats::default()
+        };
+        other.record_trip(8, 16.0);
+        total.accumulate(&other);
+        assert_eq!(
+            total,
+            ColonyStats {
+                step: 7.0,
+                food_stored: 2.0,
+                food_delivered: 2,
+                trips: 2,
+                total_trip_steps: 12.0,
+                total_trip_length: 24.0,
+                ant_count: 4,
+                births: 2,
+                deaths: 1,
+            }
+        );
+        assert_eq!(total.average_trip_steps(), 6.0);
+    }
+}
@@ -0,0 +1,133 @@
+//! Browser and WebGL helpers shared by the renderers and `lib.rs`.
+
+use wasm_bindgen::prelude::Closure;
+use wasm_bindgen::JsCast;
+use web_sys::{WebGl2RenderingContext, WebGlProgram, WebGlShader, Window};
+
+pub fn window() -> Window {
+    web_sys::window().expect("no global `window` exists")
+}
+
+pub fn request_animation_frame(f: &Closure<dyn FnMut()>) {
+    window()
+        .request_animation_frame(f.as_ref().unchecked_ref())
+        .expect("should register `requestAnimationFrame` OK");
+}
+
+pub fn compile_shader(
+    context: &WebGl2RenderingContext,
+    shader_type: u32,
+    source: &str,
+) -> Result<WebGlShader, String> {
+    let shader = context
+        .create_shader(shader_type)
+        .ok_or_else(|| String::from("Unable to create shader object"))?;
+    context.shader_source(&shader, source);
+    context.compile_shader(&shader);
+
+    if context
+        .get_shader_parameter(&shader, WebGl2RenderingContext::COMPILE_STATUS)
+        .as_bool()
+        .unwrap_or(false)
+    {
+        Ok(shader)
+    } else {
+        Err(context
+            .get_shader_info_log(&shader)
+            .unwrap_or_else(|| String::from("Unknown error creating shader")))
+    }
+}
+
+pub fn link_program(
+    context: &WebGl2RenderingContext,
+    vert_shader: &WebGlShader,
+    frag_shader: &WebGlShader,
+) -> Result<WebGlProgram, String> {
+    let program = context
+        .create_program()
+        .ok_or_else(|| String::from("Unable to create shader object"))?;
+
+    context.attach_shader(&program, vert_shader);
+    context.attach_shader(&program, frag_shader);
+    context.link_program(&program);
+
+    if context
+        .get_program_parameter(&program, WebGl2RenderingContext::LINK_STATUS)
+        .as_bool()
+        .unwrap_or(false)
+    {
+        Ok(program)
+    } else {
+        Err(context
+            .get_program_info_log(&program)
+            .unwrap_or_else(|| String::from("Unknown error creating program object")))
+    }
+}
+
+pub fn clear(gl: &WebGl2RenderingContext) {
+    gl.clear_color(0.0, 0.0, 0.0, 1.0);
+    gl.clear(WebGl2RenderingContext::COLOR_BUFFER_BIT);
+}
+
+pub fn draw_points(gl: &WebGl2RenderingContext, vert_count: i32) {
+    gl.draw_arrays(WebGl2RenderingContext::POINTS, 0, vert_count);
+}
+
+pub fn float32_array_view(data: &[f32]) -> js_sys::Float32Array {
+    let memory_buffer = wasm_bindgen::memory()
+        .dyn_into::<js_sys::WebAssembly::Memory>()
+        .unwrap()
+        .buffer();
+
+    let location: u32 = data.as_ptr() as u32 / 4;
+    let next_location = location + data.len() as u32;
+
+    js_sys::Float32Array::new(&memory_buffer).subarray(location, next_location)
+}
+
+// pub fn draw_triangles(gl: &WebGl2RenderingContext, vert_count: i32) {
+//     gl.draw_arrays(WebGl2RenderingContext::TRIANGLES, 0, vert_count);
+// }
+
+pub fn draw_instanced_triangles(gl: &WebGl2RenderingContext, vert_count: i32, instances: i32) {
+    gl.draw_arrays_instanced(WebGl2RenderingContext::TRIANGLES, 0, vert_count, instances);
+}
+
+pub fn get_canvas(window: &Window) -> web_sys::HtmlCanvasElement {
+    let document = window.document().expect("should have a document on window");
+    let canvas = document
+        .get_element_by_id("canvas")
+        .expect("document should have a #canvas element");
+    canvas
+        .dyn_into::<web_sys::HtmlCanvasElement>()
+        .map_err(|_| ())
+        .unwrap()
+}
+
+/// Maps a pointer event's CSS-pixel offset onto canvas pixel coordinates.
+pub fn pointer_position(
+    canvas: &web_sys::HtmlCanvasElement,
+    event: &web_sys::PointerEvent,
+) -> (f32, f32) {
+    let scale_x = canvas.width() as f32 / canvas.client_width().max(1) as f32;
+    let scale_y = canvas.height() as f32 / canvas.client_height().max(1) as f32;
+    (
+        event.offset_x() as f32 * scale_x,
+        event.offset_y() as f32 * scale_y,
+    )
+}
+
+pub fn get_canvas_dimensions_and_context(window: &Window) -> (f32, f32, WebGl2RenderingContext) {
+    let canvas = get_canvas(window);
+    let width: f32 = canvas.width() as f32;
+    let height: f32 = canvas.height() as f32;
+
+    let ctx = canvas
+        .get_context("webgl2")
+        .unwrap()
+        .unwrap()
+        .dyn_into::<web_sys::WebGl2RenderingContext>()
+        .unwrap();
+
+    (width, height, ctx)
+}